@@ -0,0 +1,158 @@
+//! APA102、SK9822などのクロック付きLEDのドライバです。
+//!
+//! データとクロックの2本の線で送ります。データは`out pins`で出力し、
+//! クロックはサイドセットで立ち上げるので、2本のピンは離れていても構いません。
+//! [`SmartLedsWrite`]を実装しているので、WS2812のドライバと置き換えて使えます。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{Buffers, PIOBuilder, PinDir, ShiftDirection},
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, Tx, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, Waiter, clock_divisor};
+
+/// APA102のドライバ
+///
+/// クロック付きなのでリセット時間は必要ありません。
+pub struct Apa102<P, SM, I, C>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    C: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    tx: Tx<(P, SM)>,
+    _data: I,
+    _clock: C,
+    brightness: u8,
+    waiter: Waiter,
+}
+
+impl<P, SM, I, C> Apa102<P, SM, I, C>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    C: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// `bit_rate`はクロックの周波数です。
+    pub fn new(
+        data: I,
+        clock: C,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        bit_rate: HertzU32,
+    ) -> Self {
+        // PIOに入れるプログラム
+        let program = pio_proc::pio_asm!(
+            ".side_set 1",
+            ".wrap_target",
+            "    out pins, 1        side 0",
+            "    nop                side 1",
+            ".wrap",
+        );
+
+        let installed = pio.install(&program.program).unwrap();
+
+        let (int, frac) = clock_divisor(clock_freq, bit_rate, 2);
+
+        let data = data.into();
+        let clock = clock.into();
+        let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+            .buffers(Buffers::OnlyTx)
+            .out_pins(data.id().num, 1)
+            .side_set_pin_base(clock.id().num)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(32)
+            .clock_divisor_fixed_point(int, frac)
+            .build(sm);
+        sm.set_pindirs([
+            (data.id().num, PinDir::Output),
+            (clock.id().num, PinDir::Output),
+        ]);
+        sm.start();
+
+        Self {
+            tx,
+            _data: I::from(data),
+            _clock: C::from(clock),
+            brightness: 31,
+            waiter: Waiter::default(),
+        }
+    }
+
+    /// 全てのLEDに送る5bitの明るさ(0から31)を設定します。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness.min(31);
+    }
+
+    /// 全てのLEDに送る明るさを返します。
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。
+    /// [`Ws2812Direct::set_wait_hook`](crate::Ws2812Direct::set_wait_hook)を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.waiter.set_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。
+    /// [`Ws2812Direct::set_wait_for_event`](crate::Ws2812Direct::set_wait_for_event)を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.waiter.set_irq(irq);
+    }
+
+    /// `count`個のLEDの後に送るエンドフレームを書き込みます。
+    ///
+    /// SK9822は32bitの0を、APA102はLEDの数の半分のクロックを必要とします。
+    pub(crate) fn end_frame(&mut self, count: usize) {
+        self.write_word(0);
+        for _ in 0..count.div_ceil(64) {
+            self.write_word(0);
+        }
+    }
+
+    /// FIFOに空きができるまで待ってワードを書き込みます。
+    pub(crate) fn write_word(&mut self, word: u32) {
+        while !self.tx.write(word) {
+            self.waiter.wait(&self.tx);
+        }
+    }
+}
+
+impl<P, SM, I, C> SmartLedsWrite for Apa102<P, SM, I, C>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    C: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, J>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<Self::Color>,
+    {
+        // スタートフレーム
+        self.write_word(0);
+
+        let header = (0xe0 | self.brightness as u32) << 24;
+        let mut count: usize = 0;
+        for item in iterator {
+            let color: RGB8 = item.into();
+            let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+            self.write_word(header | b << 16 | g << 8 | r);
+            count += 1;
+        }
+
+        self.end_frame(count);
+        Ok(())
+    }
+}
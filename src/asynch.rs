@@ -0,0 +1,322 @@
+//! 非同期で書き込むドライバです。`async`フィーチャーで有効になります。
+//!
+//! FIFOが一杯のときはPIOの"TX not full"割り込みで起こされるまで待つので、
+//! PIOの割り込みハンドラから[`on_interrupt`]を呼んでください。
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn PIO0_IRQ_0() {
+//!     ws2812_rp235x::asynch::on_interrupt::<PIO0>(PioIRQ::Irq0);
+//! }
+//! ```
+
+use core::{
+    cell::Cell,
+    future::poll_fn,
+    task::{Poll, Waker},
+};
+
+use critical_section::Mutex;
+#[cfg(feature = "embassy")]
+use embassy_sync::{blocking_mutex::raw::RawMutex, channel::Receiver};
+use embedded_hal_async::delay::DelayNs;
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWriteAsync};
+
+use crate::{Error, Stats, Ws2812Direct, feed::Feeder, pio_regs};
+
+struct IrqWaker(Mutex<Cell<Option<Waker>>>);
+
+impl IrqWaker {
+    const fn new() -> Self {
+        Self(Mutex::new(Cell::new(None)))
+    }
+
+    fn register(&self, waker: &Waker) {
+        critical_section::with(|cs| self.0.borrow(cs).set(Some(waker.clone())));
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = critical_section::with(|cs| self.0.borrow(cs).take()) {
+            waker.wake();
+        }
+    }
+}
+
+/// PIOブロックとステートマシンごとのWaker
+static WAKERS: [[IrqWaker; 4]; 2] = [const { [const { IrqWaker::new() }; 4] }; 2];
+
+fn irq_index(irq: PioIRQ) -> usize {
+    match irq {
+        PioIRQ::Irq0 => 0,
+        PioIRQ::Irq1 => 1,
+    }
+}
+
+/// PIOの割り込みハンドラから呼んでください。
+///
+/// "TX not full"割り込みを無効にして、待っているタスクを起こします。
+pub fn on_interrupt<P: PIOExt>(irq: PioIRQ) {
+    let sm_irq = pio_regs::<P>().sm_irq(irq_index(irq));
+    let pending = (sm_irq.irq_ints().read().bits() >> 4) & 0xf;
+    // Safety: アトミックなクリア用のエイリアスなので、他のビットには影響しません。
+    unsafe {
+        let clear = (sm_irq.irq_inte().as_ptr() as usize + 0x3000) as *mut u32;
+        clear.write_volatile(pending << 4);
+    }
+    for (sm, waker) in WAKERS[P::id()].iter().enumerate() {
+        if pending & (1 << sm) != 0 {
+            waker.wake();
+        }
+    }
+}
+
+/// 一度だけエグゼキューターに制御を返します。
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// 非同期で書き込むWS2812ドライバ
+///
+/// リセット時間の待機には`delay`を使います。
+pub struct Ws2812Async<P, SM, I, L>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    L: DelayNs,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    irq: PioIRQ,
+    delay: L,
+    /// 最後のフレームのラッチを待っていない
+    pending: bool,
+}
+
+impl<P, SM, I, L> Ws2812Async<P, SM, I, L>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    L: DelayNs,
+{
+    /// `irq`はFIFOの空きを待つときに使う割り込み線です。
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        irq: PioIRQ,
+        delay: L,
+    ) -> Self {
+        let driver = Ws2812Direct::new(pin, pio, sm, clock_freq);
+        Self {
+            driver,
+            irq,
+            delay,
+            pending: false,
+        }
+    }
+
+    /// 全体の明るさを設定します。[`Ws2812Direct::set_brightness`]を参照してください。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.driver.set_brightness(brightness);
+    }
+
+    /// 送ったフレームなどの数を返します。[`Ws2812Direct::stats`]を参照してください。
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    /// 最後のフレームの途中でFIFOが空になったかを返します。[`Ws2812Direct::underrun`]を参照してください。
+    pub fn underrun(&self) -> bool {
+        self.driver.underrun()
+    }
+
+    /// ストリップのLEDの数を設定します。[`Ws2812Direct::set_length`]を参照してください。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.driver.set_length(length);
+    }
+
+    /// フレームを送信します。
+    ///
+    /// 前のフレームがラッチされるまで待ってから送り始めるので、
+    /// アニメーションのタスクは`send_frame(frame).await`を繰り返すだけで
+    /// LEDの更新速度に合わせて進みます。
+    pub async fn send_frame<T, J>(&mut self, frame: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<RGB8>,
+    {
+        SmartLedsWriteAsync::write(self, frame).await
+    }
+
+    /// `chunk`ワード書き込むごとにエグゼキューターへ制御を返しながらフレームを送信します。
+    ///
+    /// FIFOに空きがあると`write`は一度も制御を返さないので、長いストリップで
+    /// 他のタスクを何ミリ秒も止めたくない場合はこちらを使ってください。
+    /// `chunk`が0の場合は1として扱います。
+    pub async fn write_chunked<T, J>(&mut self, iterator: T, chunk: usize) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<RGB8>,
+    {
+        self.write_frame(iterator.into_iter(), chunk.max(1)).await
+    }
+
+    /// 同期版のドライバと同じ処理で、`chunk`ワードごとに制御を返しながらフレームを書き込みます。
+    ///
+    /// ストリップの長さの確認と消灯のデータ、FIFOが空になったかの検出と統計も同じです。
+    async fn write_frame<T>(&mut self, iter: T, chunk: usize) -> Result<(), Error>
+    where
+        T: Iterator,
+        T::Item: Into<RGB8>,
+    {
+        let mut frame = self.driver.start_frame(Feeder::new(iter))?;
+        self.flush().await;
+        self.pending = true;
+        loop {
+            if let Some(result) = self.driver.feed_step(&mut frame, chunk) {
+                return result;
+            }
+            if self.driver.tx.is_full() {
+                self.wait_not_full().await;
+            } else {
+                yield_now().await;
+            }
+        }
+    }
+
+    /// FIFOに空きができるまで待ちます。
+    async fn wait_not_full(&mut self) {
+        let tx = &mut self.driver.tx;
+        let irq = self.irq;
+        poll_fn(|cx| {
+            if !tx.is_full() {
+                return Poll::Ready(());
+            }
+            WAKERS[P::id()][SM::id()].register(cx.waker());
+            tx.enable_tx_not_full_interrupt(irq);
+            if !tx.is_full() {
+                tx.disable_tx_not_full_interrupt(irq);
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// 前のフレームを送り終え、リセット時間が経つまで待ちます。
+    ///
+    /// 戻った時点でLEDの表示は最後に書き込んだフレームに変わっています。
+    /// 既にラッチ済みの場合はすぐに戻ります。
+    pub async fn flush(&mut self) {
+        if !self.pending {
+            return;
+        }
+        let word_micros = self.driver.word_micros();
+        while !self.driver.tx.is_empty() {
+            self.delay.delay_us(word_micros).await;
+        }
+        // OSRに残っているワードの分も待ちます。
+        self.delay
+            .delay_us(word_micros + self.driver.reset_micros())
+            .await;
+        self.pending = false;
+    }
+
+    /// [`flush`](Self::flush)と同じです。
+    pub async fn wait_idle(&mut self) {
+        self.flush().await
+    }
+}
+
+/// リセット時間を`embassy_time::Timer`で待つドライバ
+#[cfg(feature = "embassy")]
+pub type Ws2812Embassy<P, SM, I> = Ws2812Async<P, SM, I, embassy_time::Delay>;
+
+#[cfg(feature = "embassy")]
+impl<P, SM, I> Ws2812Async<P, SM, I, embassy_time::Delay>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// リセット時間の待機に`embassy_time::Timer`を使います。
+    pub fn new_embassy(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        irq: PioIRQ,
+    ) -> Self {
+        Self::new(pin, pio, sm, clock_freq, irq, embassy_time::Delay)
+    }
+}
+
+#[cfg(feature = "embassy")]
+impl<P, SM, I, L> Ws2812Async<P, SM, I, L>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    L: DelayNs,
+{
+    /// `frames`から受け取ったフレームを送り続けます。
+    ///
+    /// ドライバを所有するタスクの中で呼んでください。
+    /// 複数のタスクは`embassy_sync::channel::Channel`の`Sender`からフレームを送るだけで済みます。
+    ///
+    /// ```ignore
+    /// static FRAMES: Channel<CriticalSectionRawMutex, [RGB8; 64], 2> = Channel::new();
+    ///
+    /// #[embassy_executor::task]
+    /// async fn leds(ws: Ws2812Embassy<PIO0, SM0, Pin>) -> ! {
+    ///     ws.run(FRAMES.receiver()).await
+    /// }
+    /// ```
+    pub async fn run<M, F, const N: usize>(mut self, frames: Receiver<'_, M, F, N>) -> !
+    where
+        M: RawMutex,
+        F: IntoIterator,
+        F::Item: Into<RGB8>,
+    {
+        loop {
+            let frame = frames.receive().await;
+            let _ = self.send_frame(frame).await;
+        }
+    }
+}
+
+impl<P, SM, I, L> SmartLedsWriteAsync for Ws2812Async<P, SM, I, L>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    L: DelayNs,
+{
+    type Color = RGB8;
+    type Error = Error;
+    async fn write<T, J>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<Self::Color>,
+    {
+        self.write_frame(iterator.into_iter(), usize::MAX).await
+    }
+}
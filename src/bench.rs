@@ -0,0 +1,89 @@
+//! 変換と送信の速さを測るベンチマークです。
+//!
+//! `bench`フィーチャーを有効にすると使えます。
+//! 実際のクロックの設定で、ブロッキング、割り込み、DMAのそれぞれの送り方を比べられます。
+//!
+//! ```ignore
+//! let encode = bench::encode(&timer, PixelFormat::Grb, 1000);
+//! let blocking = bench::write(&timer, &mut ws, 300)?;
+//! let dma = bench::measure(&timer, 300, || {
+//!     let (d, b) = dma.take().unwrap().write(buffer.take().unwrap()).wait();
+//!     (dma, buffer) = (Some(d), Some(b));
+//! });
+//! ```
+
+use core::hint::black_box;
+
+use rp235x_hal::{
+    fugit::{ExtU32, MicrosDurationU32},
+    timer::{Timer, TimerDevice},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::PixelFormat;
+
+/// 測った結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    /// 処理したピクセルの数
+    pub pixels: usize,
+    /// かかった時間
+    pub elapsed: MicrosDurationU32,
+}
+
+impl Report {
+    /// 1秒あたりに処理できるピクセルの数を返します。
+    pub fn pixels_per_sec(&self) -> u32 {
+        let micros = self.elapsed.to_micros().max(1) as u64;
+        (self.pixels as u64 * 1_000_000 / micros).min(u32::MAX as u64) as u32
+    }
+}
+
+/// `f`にかかった時間を測ります。
+///
+/// 割り込みやDMAで送る場合は、送り終えるまで待つ処理も`f`に含めてください。
+pub fn measure<D, F>(timer: &Timer<D>, pixels: usize, f: F) -> Report
+where
+    D: TimerDevice,
+    F: FnOnce(),
+{
+    let start = timer.get_counter();
+    f();
+    let elapsed = timer
+        .get_counter()
+        .checked_duration_since(start)
+        .map_or(0, |elapsed| elapsed.to_micros());
+    Report {
+        pixels,
+        elapsed: (elapsed.min(u32::MAX as u64) as u32).micros(),
+    }
+}
+
+/// `format`で`pixels`個のピクセルをワードに変換する速さを測ります。
+pub fn encode<D>(timer: &Timer<D>, format: PixelFormat, pixels: usize) -> Report
+where
+    D: TimerDevice,
+{
+    measure(timer, pixels, || {
+        for i in 0..pixels {
+            let color = RGB8::new(i as u8, (i >> 8) as u8, !(i as u8));
+            black_box(format.encode(black_box(color)));
+        }
+    })
+}
+
+/// `driver`で`pixels`個のピクセルのフレームを書き込む速さを測ります。
+///
+/// 書き込みが終わるまでの時間なので、FIFOに残ったワードの送信時間は含みません。
+pub fn write<D, W>(timer: &Timer<D>, driver: &mut W, pixels: usize) -> Result<Report, W::Error>
+where
+    D: TimerDevice,
+    W: SmartLedsWrite,
+    RGB8: Into<W::Color>,
+{
+    let mut result = Ok(());
+    let report = measure(timer, pixels, || {
+        result = driver.write((0..pixels).map(|i| RGB8::new(i as u8, 0, 0)));
+    });
+    result.map(|()| report)
+}
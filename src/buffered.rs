@@ -0,0 +1,309 @@
+//! フレームバッファを持つドライバです。
+
+use core::ops::Range;
+
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::compose::lerp;
+
+/// `N`個のLEDのフレームバッファを持つドライバ
+///
+/// ピクセルを書き換えてから[`flush`](Self::flush)でまとめて送ります。
+/// 前回送ってから何も変わっていない場合、`flush`は何も送りません。
+/// `driver`には[`Ws2812`](crate::Ws2812)など、[`RGB8`]を受け取る任意のドライバを使えます。
+pub struct Ws2812Buffered<W, const N: usize>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    driver: W,
+    buffer: [RGB8; N],
+    /// 前回送ってからフレームバッファが変わった
+    dirty: bool,
+}
+
+impl<W, const N: usize> Ws2812Buffered<W, N>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    /// 全てのピクセルが消灯した状態で作ります。
+    pub fn new(driver: W) -> Self {
+        Self {
+            driver,
+            buffer: [RGB8::default(); N],
+            dirty: true,
+        }
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> W {
+        self.driver
+    }
+
+    /// `index`番目のピクセルの色を設定します。範囲外の場合は何もしません。
+    ///
+    /// [`Hsv`](crate::Hsv)など、[`RGB8`]に変換できる色を渡せます。
+    pub fn set_pixel(&mut self, index: usize, color: impl Into<RGB8>) {
+        let color = color.into();
+        if let Some(pixel) = self.buffer.get_mut(index)
+            && *pixel != color
+        {
+            *pixel = color;
+            self.dirty = true;
+        }
+    }
+
+    /// `index`番目のピクセルの色を返します。
+    pub fn pixel(&self, index: usize) -> Option<RGB8> {
+        self.buffer.get(index).copied()
+    }
+
+    /// 全てのピクセルを`color`にします。
+    pub fn fill(&mut self, color: impl Into<RGB8>) {
+        let color = color.into();
+        if self.buffer.iter().any(|&pixel| pixel != color) {
+            self.buffer = [color; N];
+            self.dirty = true;
+        }
+    }
+
+    /// 全てのピクセルを消灯します。
+    pub fn clear(&mut self) {
+        self.fill(RGB8::default());
+    }
+
+    /// `range`のピクセルを`color`にします。範囲外の部分は無視します。
+    pub fn draw_range(&mut self, range: Range<usize>, color: impl Into<RGB8>) {
+        let color = color.into();
+        let range = range.start.min(N)..range.end.min(N);
+        if !range.is_empty() {
+            self.buffer[range].fill(color);
+            self.dirty = true;
+        }
+    }
+
+    /// `range`のピクセルを`start`から`end`へのグラデーションにします。範囲外の部分は無視します。
+    ///
+    /// `range`の最初のピクセルが`start`、最後のピクセルが`end`になります。
+    pub fn draw_gradient(
+        &mut self,
+        range: Range<usize>,
+        start: impl Into<RGB8>,
+        end: impl Into<RGB8>,
+    ) {
+        let (start, end) = (start.into(), end.into());
+        let len = range.len();
+        let visible = range.start.min(N)..range.end.min(N);
+        for index in visible {
+            let i = index - range.start;
+            let t = if len > 1 { i * 255 / (len - 1) } else { 0 };
+            self.buffer[index] = lerp(start, end, t as u8);
+            self.dirty = true;
+        }
+    }
+
+    /// 全てのピクセルを`start`から`end`へのグラデーションにします。
+    pub fn fill_gradient(&mut self, start: impl Into<RGB8>, end: impl Into<RGB8>) {
+        self.draw_gradient(0..N, start, end);
+    }
+
+    /// 1/256ピクセル単位の位置`position`に点を描きます。
+    ///
+    /// 明るさを隣り合う2つのピクセルに位置の割合で分け、元の色に足し合わせる(255で飽和)ので、
+    /// 動く点や彗星のようなエフェクトがピクセルごとに飛ばず滑らかに動きます。
+    pub fn draw_point(&mut self, position: u32, color: impl Into<RGB8>) {
+        let color = color.into();
+        let index = (position >> 8) as usize;
+        let frac = (position & 0xff) as u8;
+        let black = RGB8::default();
+        for (index, weight) in [(index, 255 - frac), (index + 1, frac)] {
+            if let Some(pixel) = self.buffer.get_mut(index)
+                && weight != 0
+            {
+                let add = lerp(black, color, weight);
+                *pixel = RGB8::new(
+                    pixel.r.saturating_add(add.r),
+                    pixel.g.saturating_add(add.g),
+                    pixel.b.saturating_add(add.b),
+                );
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// 全てのピクセルの色を`shader(index, t)`で計算します。
+    ///
+    /// `t`はフレームの時刻で、単位は呼び出し側で決めてください(ミリ秒など)。
+    pub fn render<F>(&mut self, t: u32, mut shader: F)
+    where
+        F: FnMut(usize, u32) -> RGB8,
+    {
+        for (index, pixel) in self.buffer.iter_mut().enumerate() {
+            *pixel = shader(index, t);
+        }
+        self.dirty = true;
+    }
+
+    /// 全てのピクセルを`n`個先頭側へ回転します。先頭のピクセルは末尾に回ります。
+    pub fn rotate_left(&mut self, n: usize) {
+        if N > 0 {
+            self.buffer.rotate_left(n % N);
+            self.dirty = true;
+        }
+    }
+
+    /// 全てのピクセルを`n`個末尾側へ回転します。末尾のピクセルは先頭に回ります。
+    pub fn rotate_right(&mut self, n: usize) {
+        if N > 0 {
+            self.buffer.rotate_right(n % N);
+            self.dirty = true;
+        }
+    }
+
+    /// 全てのピクセルを1個末尾側へずらし、先頭を`color`にします。
+    ///
+    /// 末尾のピクセルは捨てられます。
+    pub fn shift_in(&mut self, color: impl Into<RGB8>) {
+        self.scroll(1, color);
+    }
+
+    /// 全てのピクセルを`offset`個ずらし、空いたピクセルを`fill`にします。
+    ///
+    /// 正の値で末尾側へ、負の値で先頭側へずらします。はみ出したピクセルは捨てられます。
+    pub fn scroll(&mut self, offset: isize, fill: impl Into<RGB8>) {
+        let fill = fill.into();
+        let n = offset.unsigned_abs().min(N);
+        if n == 0 {
+            return;
+        }
+        if offset > 0 {
+            self.buffer.copy_within(..N - n, n);
+            self.buffer[..n].fill(fill);
+        } else {
+            self.buffer.copy_within(n.., 0);
+            self.buffer[N - n..].fill(fill);
+        }
+        self.dirty = true;
+    }
+
+    /// フレームバッファを`snapshot`に保存し、保存したピクセルの数を返します。
+    ///
+    /// 警告などで一時的に表示を上書きする前に保存し、[`restore`](Self::restore)で戻せます。
+    pub fn snapshot(&self, snapshot: &mut [RGB8]) -> usize {
+        let len = snapshot.len().min(N);
+        snapshot[..len].copy_from_slice(&self.buffer[..len]);
+        len
+    }
+
+    /// `snapshot`の内容をフレームバッファの先頭から書き戻します。
+    pub fn restore(&mut self, snapshot: &[RGB8]) {
+        let len = snapshot.len().min(N);
+        if self.buffer[..len] != snapshot[..len] {
+            self.buffer[..len].copy_from_slice(&snapshot[..len]);
+            self.dirty = true;
+        }
+    }
+
+    /// フレームバッファを返します。
+    pub fn pixels(&self) -> &[RGB8; N] {
+        &self.buffer
+    }
+
+    /// 書き換えられるフレームバッファを返します。
+    ///
+    /// 実際に書き換えたかに関わらず、次の`flush`で送ります。
+    pub fn pixels_mut(&mut self) -> &mut [RGB8; N] {
+        self.dirty = true;
+        &mut self.buffer
+    }
+
+    /// 前回送ってからフレームバッファが変わったかを返します。
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// 変わっていなくても次の`flush`で送るようにします。
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// フレームバッファが変わっていれば送ります。
+    pub fn flush(&mut self) -> Result<(), W::Error> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.driver.write(self.buffer.iter().copied())?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// 表と裏の2つのフレームバッファを持つドライバ
+///
+/// 裏のバッファに描いてから[`swap`](Self::swap)で入れ替え、表のバッファを送ります。
+/// 入れ替えは添字を切り替えるだけなので、送っている途中のフレームが書き換わることはありません。
+pub struct DoubleBuffered<W, const N: usize>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    driver: W,
+    buffers: [[RGB8; N]; 2],
+    /// 表のバッファの添字
+    front: usize,
+}
+
+impl<W, const N: usize> DoubleBuffered<W, N>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    /// 両方のバッファが消灯した状態で作ります。
+    pub fn new(driver: W) -> Self {
+        Self {
+            driver,
+            buffers: [[RGB8::default(); N]; 2],
+            front: 0,
+        }
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> W {
+        self.driver
+    }
+
+    /// 表のバッファを返します。
+    pub fn front(&self) -> &[RGB8; N] {
+        &self.buffers[self.front]
+    }
+
+    /// 裏のバッファを返します。
+    pub fn back_mut(&mut self) -> &mut [RGB8; N] {
+        &mut self.buffers[1 - self.front]
+    }
+
+    /// 表のバッファと裏のバッファを同時に返します。
+    pub fn split_mut(&mut self) -> (&[RGB8; N], &mut [RGB8; N]) {
+        let [first, second] = &mut self.buffers;
+        if self.front == 0 {
+            (first, second)
+        } else {
+            (second, first)
+        }
+    }
+
+    /// 表と裏を入れ替えます。
+    ///
+    /// 入れ替えた後の裏のバッファには、それまで表だったフレームが残っています。
+    pub fn swap(&mut self) {
+        self.front = 1 - self.front;
+    }
+
+    /// 表のバッファを送ります。
+    pub fn flush(&mut self) -> Result<(), W::Error> {
+        self.driver.write(self.buffers[self.front].iter().copied())
+    }
+
+    /// 表と裏を入れ替えて、新しい表のバッファを送ります。
+    pub fn present(&mut self) -> Result<(), W::Error> {
+        self.swap();
+        self.flush()
+    }
+}
@@ -0,0 +1,52 @@
+//! LEDのチップごとの設定です。
+
+use crate::{PixelFormat, RESET_MICROS, Timing};
+
+/// LEDのチップ
+///
+/// タイミング、リセット時間、データの並びをチップの名前で選べます。
+/// 互換品でWS2812Bの設定が合わない場合に使ってください。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chip {
+    #[default]
+    Ws2812B,
+    /// WS2811の高速モード (800kHz)
+    Ws2811,
+    /// WS2811の低速モード (400kHz)
+    Ws2811Slow,
+    Ws2813,
+    Ws2815,
+    Sk6812,
+    Sk6805,
+    Apa106,
+}
+
+impl Chip {
+    /// 1ビットの波形のタイミングを返します。
+    pub fn timing(self) -> Timing {
+        match self {
+            Chip::Ws2812B | Chip::Ws2811 | Chip::Ws2813 | Chip::Ws2815 => Timing::WS2812,
+            Chip::Ws2811Slow => Timing::WS2811_400KHZ,
+            Chip::Sk6812 | Chip::Sk6805 => Timing::SK6812,
+            Chip::Apa106 => Timing::APA106,
+        }
+    }
+
+    /// ラッチに必要なリセット時間(µs)を返します。
+    pub fn reset_micros(self) -> u32 {
+        match self {
+            Chip::Ws2812B => RESET_MICROS,
+            Chip::Ws2811 | Chip::Ws2811Slow | Chip::Apa106 => 60,
+            Chip::Ws2813 | Chip::Ws2815 => 300,
+            Chip::Sk6812 | Chip::Sk6805 => 90,
+        }
+    }
+
+    /// LEDが受け取るデータの並びを返します。
+    pub fn format(self) -> PixelFormat {
+        match self {
+            Chip::Ws2811 | Chip::Ws2811Slow | Chip::Apa106 => PixelFormat::Rgb,
+            _ => PixelFormat::Grb,
+        }
+    }
+}
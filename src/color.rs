@@ -0,0 +1,123 @@
+//! 色の表現です。
+
+use smart_leds_trait::{RGB8, RGB16, SmartLedsWrite};
+
+use crate::Gamma;
+
+/// 色相、彩度、明度で表した色
+///
+/// [`RGB8`]に変換できるので、そのまま`write`や[`Ws2812Buffered`](crate::Ws2812Buffered)に渡せます。
+/// 変換は整数演算だけで行います。明度は線形な値として扱うので、
+/// 見た目の明るさを揃えたい場合は[`Hsv::to_rgb_gamma`]を使ってください。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Hsv {
+    /// 色相(0から255で1周)
+    pub hue: u8,
+    /// 彩度
+    pub sat: u8,
+    /// 明度
+    pub val: u8,
+}
+
+impl Hsv {
+    pub const fn new(hue: u8, sat: u8, val: u8) -> Self {
+        Self { hue, sat, val }
+    }
+
+    /// [`RGB8`]に変換します。
+    pub const fn to_rgb(self) -> RGB8 {
+        let Hsv { hue, sat, val } = self;
+        if sat == 0 {
+            return RGB8 {
+                r: val,
+                g: val,
+                b: val,
+            };
+        }
+        let (h, s, v) = (hue as u32, sat as u32, val as u32);
+        let region = h / 43;
+        let rem = (h - region * 43) * 6;
+        let p = ((v * (255 - s)) >> 8) as u8;
+        let q = ((v * (255 - ((s * rem) >> 8))) >> 8) as u8;
+        let t = ((v * (255 - ((s * (255 - rem)) >> 8))) >> 8) as u8;
+        let (r, g, b) = match region {
+            0 => (val, t, p),
+            1 => (q, val, p),
+            2 => (p, val, t),
+            3 => (p, q, val),
+            4 => (t, p, val),
+            _ => (val, p, q),
+        };
+        RGB8 { r, g, b }
+    }
+
+    /// [`RGB8`]に変換し、各チャンネルに`gamma`の補正をかけます。
+    ///
+    /// 明度を変えたときに、見た目の明るさが均等に変わります。
+    pub const fn to_rgb_gamma(self, gamma: &Gamma) -> RGB8 {
+        let RGB8 { r, g, b } = self.to_rgb();
+        RGB8 {
+            r: gamma.apply(r),
+            g: gamma.apply(g),
+            b: gamma.apply(b),
+        }
+    }
+}
+
+impl From<Hsv> for RGB8 {
+    fn from(hsv: Hsv) -> Self {
+        hsv.to_rgb()
+    }
+}
+
+/// 16bitの色を四捨五入して8bitにします。
+pub const fn downscale(color: RGB16) -> RGB8 {
+    const fn channel(value: u16) -> u8 {
+        ((value as u32 * 255 + 32767) / 65535) as u8
+    }
+    RGB8 {
+        r: channel(color.r),
+        g: channel(color.g),
+        b: channel(color.b),
+    }
+}
+
+/// 16bitの色を受け取り、8bitに丸めて送るドライバ
+///
+/// 高い精度で計算した色を、呼び出し側で丸めずにそのまま渡せます。
+pub struct Downscaled<W>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    driver: W,
+}
+
+impl<W> Downscaled<W>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    pub fn new(driver: W) -> Self {
+        Self { driver }
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> W {
+        self.driver
+    }
+}
+
+impl<W> SmartLedsWrite for Downscaled<W>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    type Color = RGB16;
+    type Error = W::Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), W::Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        self.driver
+            .write(iterator.into_iter().map(|item| downscale(item.into())))
+    }
+}
@@ -0,0 +1,178 @@
+//! 複数のレイヤーを重ねて1つのフレームにする処理です。
+//!
+//! 背景のアニメーションと状態表示のように、別々のコードが描いたレイヤーを
+//! 送信の直前に重ねられます。
+//!
+//! ```ignore
+//! let layers = [Layer::new(&ambient), Layer::new(&status).alpha(128)];
+//! ws.write(Composite::new(layers))?;
+//! ```
+
+use smart_leds_trait::RGB8;
+
+/// レイヤーの色と下の色の合成方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// レイヤーの色で置き換える
+    #[default]
+    Normal,
+    /// 足し合わせる(255で飽和)
+    Add,
+    /// 掛け合わせる(暗くなる)
+    Multiply,
+    /// スクリーン(明るくなる)
+    Screen,
+    /// 明るい方を選ぶ
+    Max,
+}
+
+impl BlendMode {
+    /// `below`の上に`color`を合成した色を返します。
+    fn apply(self, below: RGB8, color: RGB8) -> RGB8 {
+        let channel = |below: u8, color: u8| match self {
+            BlendMode::Normal => color,
+            BlendMode::Add => below.saturating_add(color),
+            BlendMode::Multiply => ((below as u32 * color as u32 + 127) / 255) as u8,
+            BlendMode::Screen => {
+                255 - (((255 - below) as u32 * (255 - color) as u32 + 127) / 255) as u8
+            }
+            BlendMode::Max => below.max(color),
+        };
+        RGB8::new(
+            channel(below.r, color.r),
+            channel(below.g, color.g),
+            channel(below.b, color.b),
+        )
+    }
+}
+
+/// ピクセルごとのマスク
+///
+/// `bits[i / 8]`の下から`i % 8`ビット目が1のピクセルだけが対象になります。
+/// 範囲外のピクセルは対象外です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mask<'a> {
+    bits: &'a [u8],
+}
+
+impl<'a> Mask<'a> {
+    pub const fn new(bits: &'a [u8]) -> Self {
+        Self { bits }
+    }
+
+    /// `index`番目のピクセルが対象かを返します。
+    pub fn contains(&self, index: usize) -> bool {
+        self.bits
+            .get(index / 8)
+            .is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+    }
+}
+
+/// 重ねるレイヤー
+#[derive(Debug, Clone, Copy)]
+pub struct Layer<'a> {
+    pixels: &'a [RGB8],
+    alpha: u8,
+    mode: BlendMode,
+    mask: Option<Mask<'a>>,
+}
+
+impl<'a> Layer<'a> {
+    /// 不透明なレイヤーを作ります。
+    pub fn new(pixels: &'a [RGB8]) -> Self {
+        Self {
+            pixels,
+            alpha: 255,
+            mode: BlendMode::Normal,
+            mask: None,
+        }
+    }
+
+    /// レイヤー全体の不透明度を設定します。0で透明、255で不透明です。
+    pub fn alpha(mut self, alpha: u8) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// 下のレイヤーとの合成方法を設定します。
+    ///
+    /// 合成した色と下の色を、不透明度の割合で混ぜます。
+    pub fn mode(mut self, mode: BlendMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// `mask`のピクセルだけに重ねるようにします。
+    ///
+    /// エフェクトはストリップ全体に描き、実際の配置に合わせたマスクで範囲を絞れます。
+    pub fn mask(mut self, mask: Mask<'a>) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    /// `index`番目のピクセルを`below`の上に重ねます。
+    fn blend(&self, index: usize, below: RGB8) -> RGB8 {
+        if let Some(mask) = &self.mask
+            && !mask.contains(index)
+        {
+            return below;
+        }
+        match self.pixels.get(index) {
+            Some(&color) => lerp(below, self.mode.apply(below, color), self.alpha),
+            None => below,
+        }
+    }
+}
+
+/// `from`から`to`へ`t`/255だけ近づけます。
+pub(crate) fn lerp(from: RGB8, to: RGB8, t: u8) -> RGB8 {
+    let mix = |from: u8, to: u8| {
+        let (from, to, t) = (from as u32, to as u32, t as u32);
+        ((from * (255 - t) + to * t + 127) / 255) as u8
+    };
+    RGB8::new(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
+/// レイヤーを下から順に重ねたフレームを返すイテレーター
+///
+/// フレームの長さは最初のレイヤーの長さです。
+/// 後のレイヤーが短い場合、はみ出した部分は下のレイヤーがそのまま見えます。
+pub struct Composite<'a, const L: usize> {
+    layers: [Layer<'a>; L],
+    index: usize,
+}
+
+impl<'a, const L: usize> Composite<'a, L> {
+    /// `layers`は下のレイヤーから順に並べてください。
+    pub fn new(layers: [Layer<'a>; L]) -> Self {
+        Self { layers, index: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.layers.first().map_or(0, |layer| layer.pixels.len())
+    }
+}
+
+impl<const L: usize> Iterator for Composite<'_, L> {
+    type Item = RGB8;
+
+    fn next(&mut self) -> Option<RGB8> {
+        if self.index >= self.len() {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        let color = self
+            .layers
+            .iter()
+            .fold(RGB8::default(), |below, layer| layer.blend(index, below));
+        Some(color)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const L: usize> ExactSizeIterator for Composite<'_, L> {}
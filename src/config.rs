@@ -0,0 +1,107 @@
+//! ドライバの設定です。
+
+use rp235x_hal::fugit::HertzU32;
+
+use crate::{Chip, PixelFormat, ProgramVariant, RESET_MICROS, Timing};
+
+/// [`Ws2812Direct::with_config`](crate::Ws2812Direct::with_config)に渡す設定
+///
+/// 既定値はWS2812Bの設定です。
+///
+/// ```ignore
+/// let config = Config::new()
+///     .timing(Timing::new(3, 4, 3, 800.kHz()))
+///     .reset_micros(100);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub(crate) timing: Timing,
+    pub(crate) reset_micros: u32,
+    pub(crate) format: PixelFormat,
+    pub(crate) program: ProgramVariant,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    pub const fn new() -> Self {
+        Self {
+            timing: Timing::WS2812,
+            reset_micros: RESET_MICROS,
+            format: PixelFormat::Grb,
+            program: ProgramVariant::Standard,
+        }
+    }
+
+    /// 1ビットの波形のタイミングを設定します。
+    pub const fn timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// T1、T2、T3のサイクル数を設定します。ビットレートは変わりません。
+    pub const fn cycles(mut self, t1: u8, t2: u8, t3: u8) -> Self {
+        self.timing = Timing::new(t1, t2, t3, self.timing.bit_rate());
+        self
+    }
+
+    /// ビットレートを設定します。
+    pub const fn bit_rate(mut self, bit_rate: HertzU32) -> Self {
+        self.timing = Timing::new(
+            self.timing.t1(),
+            self.timing.t2(),
+            self.timing.t3(),
+            bit_rate,
+        );
+        self
+    }
+
+    /// ラッチに必要なリセット時間(µs)を設定します。
+    pub const fn reset_micros(mut self, reset_micros: u32) -> Self {
+        self.reset_micros = reset_micros;
+        self
+    }
+
+    /// 使うPIOのプログラムを設定します。
+    pub const fn program(mut self, program: ProgramVariant) -> Self {
+        self.program = program;
+        self
+    }
+
+    /// `leds`個のLEDに1フレームを送り、リセット時間が経つまでの時間(µs)を返します。
+    pub const fn frame_micros(&self, leds: usize) -> u64 {
+        let bits = leds as u64 * self.format.bits() as u64;
+        (bits * 1_000_000).div_ceil(self.timing.bit_rate().to_Hz() as u64)
+            + self.reset_micros as u64
+    }
+
+    /// `fps`で更新し続けられるLEDの最大の数を返します。
+    pub const fn max_leds(&self, fps: u32) -> usize {
+        let period = 1_000_000 / if fps == 0 { 1 } else { fps } as u64;
+        if period <= self.reset_micros as u64 {
+            return 0;
+        }
+        let bits =
+            (period - self.reset_micros as u64) * self.timing.bit_rate().to_Hz() as u64 / 1_000_000;
+        (bits / self.format.bits() as u64) as usize
+    }
+
+    /// LEDが受け取るデータの並びを設定します。
+    pub const fn format(mut self, format: PixelFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl From<Chip> for Config {
+    fn from(chip: Chip) -> Self {
+        Self::new()
+            .timing(chip.timing())
+            .reset_micros(chip.reset_micros())
+            .format(chip.format())
+    }
+}
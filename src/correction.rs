@@ -0,0 +1,436 @@
+//! 送信前に色を補正する処理です。
+//!
+//! [`Corrected`]でドライバを包むと、書き込む全ての色に補正がかかります。
+//!
+//! ```ignore
+//! static GAMMA: Gamma = Gamma::new(2.8);
+//! let mut ws = Corrected::new(ws, &GAMMA);
+//! ```
+
+use smart_leds_trait::{RGB8, RGBW, SmartLedsWrite};
+
+use crate::Strip;
+
+/// 色の補正
+pub trait Correction {
+    fn correct(&self, color: RGB8) -> RGB8;
+}
+
+impl<C: Correction + ?Sized> Correction for &C {
+    fn correct(&self, color: RGB8) -> RGB8 {
+        (**self).correct(color)
+    }
+}
+
+/// `A`の後に`B`をかけます。
+impl<A: Correction, B: Correction> Correction for (A, B) {
+    fn correct(&self, color: RGB8) -> RGB8 {
+        self.1.correct(self.0.correct(color))
+    }
+}
+
+/// `x`の自然対数を返します。`x`は正の値にしてください。
+const fn ln(mut x: f64) -> f64 {
+    // x = m * 2^e (0.5 <= m < 1)に分けます。
+    let mut e = 0;
+    while x < 0.5 {
+        x *= 2.0;
+        e -= 1;
+    }
+    while x >= 1.0 {
+        x /= 2.0;
+        e += 1;
+    }
+    // ln(m) = 2 * atanh((m - 1) / (m + 1))
+    let y = (x - 1.0) / (x + 1.0);
+    let y2 = y * y;
+    let mut term = y;
+    let mut sum = 0.0;
+    let mut k = 1;
+    while k < 40 {
+        sum += term / k as f64;
+        term *= y2;
+        k += 2;
+    }
+    2.0 * sum + e as f64 * core::f64::consts::LN_2
+}
+
+/// `e^x`を返します。
+const fn exp(x: f64) -> f64 {
+    // e^x = (e^(x / 256))^256
+    let x = x / 256.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut k = 1;
+    while k < 12 {
+        term *= x / k as f64;
+        sum += term;
+        k += 1;
+    }
+    let mut i = 0;
+    while i < 8 {
+        sum *= sum;
+        i += 1;
+    }
+    sum
+}
+
+/// ガンマ補正
+///
+/// 256個の値の表をコンパイル時に作れるので、`static`や`const`に置いてください。
+/// WS2812のPWMは明るさに対して線形なので、sRGBの色をそのまま送ると白っぽく見えます。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gamma {
+    table: [u8; 256],
+}
+
+impl Gamma {
+    /// よく使われるガンマ2.8の補正
+    pub const DEFAULT: Gamma = Gamma::new(2.8);
+
+    /// `gamma`乗の補正を作ります。
+    pub const fn new(gamma: f32) -> Self {
+        let gamma = gamma as f64;
+        let mut table = [0; 256];
+        let mut i = 1;
+        while i < 256 {
+            let value = exp(gamma * ln(i as f64 / 255.0)) * 255.0 + 0.5;
+            table[i] = if value >= 255.0 { 255 } else { value as u8 };
+            i += 1;
+        }
+        Self { table }
+    }
+
+    /// CIE 1931の明度の曲線
+    ///
+    /// べき乗のガンマの代わりに使うと、明るさを変えたときに人の目には均等に変わって見えます。
+    pub const CIE1931: Gamma = Gamma::cie1931();
+
+    const fn cie1931() -> Self {
+        let mut table = [0; 256];
+        let mut i = 1;
+        while i < 256 {
+            let l = i as f64 * 100.0 / 255.0;
+            let y = if l <= 8.0 {
+                l / 903.3
+            } else {
+                let t = (l + 16.0) / 116.0;
+                t * t * t
+            };
+            let value = y * 255.0 + 0.5;
+            table[i] = if value >= 255.0 { 255 } else { value as u8 };
+            i += 1;
+        }
+        Self { table }
+    }
+
+    /// 1つのチャンネルの値を補正します。
+    pub const fn apply(&self, value: u8) -> u8 {
+        self.table[value as usize]
+    }
+}
+
+impl Correction for Gamma {
+    fn correct(&self, color: RGB8) -> RGB8 {
+        RGB8::new(
+            self.apply(color.r),
+            self.apply(color.g),
+            self.apply(color.b),
+        )
+    }
+}
+
+/// チャンネルごとのガンマ補正
+///
+/// LEDのチャンネルごとの暗いところでの明るさの違いを揃えます。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelGamma {
+    r: Gamma,
+    g: Gamma,
+    b: Gamma,
+}
+
+impl ChannelGamma {
+    /// チャンネルごとに`gamma`乗の補正を作ります。
+    pub const fn new(r: f32, g: f32, b: f32) -> Self {
+        Self::from_curves(Gamma::new(r), Gamma::new(g), Gamma::new(b))
+    }
+
+    /// チャンネルごとの補正の表から作ります。
+    pub const fn from_curves(r: Gamma, g: Gamma, b: Gamma) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl Correction for ChannelGamma {
+    fn correct(&self, color: RGB8) -> RGB8 {
+        RGB8::new(
+            self.r.apply(color.r),
+            self.g.apply(color.g),
+            self.b.apply(color.b),
+        )
+    }
+}
+
+/// 明るさの設定値を実際の明るさに変換する曲線
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DimmingCurve {
+    /// 設定値に比例
+    #[default]
+    Linear,
+    /// 対数(設定値が1増えるごとに一定の比率で明るくなる)
+    ///
+    /// 256の設定値乗から1を引いた値で、暗いところを細かく調整できます。
+    Logarithmic,
+    /// 二乗(DMXの調光卓でよく使われるスクエアロー)
+    SquareLaw,
+}
+
+/// 対数の調光曲線の表
+static LOGARITHMIC: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = clamp_u8(pow(256.0, i as f64 / 255.0) - 1.0);
+        i += 1;
+    }
+    table
+};
+
+impl DimmingCurve {
+    /// 明るさの設定値を実際の明るさに変換します。
+    pub fn apply(self, level: u8) -> u8 {
+        match self {
+            DimmingCurve::Linear => level,
+            DimmingCurve::Logarithmic => LOGARITHMIC[level as usize],
+            DimmingCurve::SquareLaw => ((level as u16 * level as u16 + 127) / 255) as u8,
+        }
+    }
+}
+
+/// `x^y`を返します。`x`は正の値にしてください。
+const fn pow(x: f64, y: f64) -> f64 {
+    exp(y * ln(x))
+}
+
+/// 0から255に丸めます。
+const fn clamp_u8(value: f64) -> u8 {
+    if value <= 0.0 {
+        0
+    } else if value >= 255.0 {
+        255
+    } else {
+        (value + 0.5) as u8
+    }
+}
+
+/// ホワイトバランスの補正
+///
+/// WS2812の白は青みが強く、ロットによっても違うので、チャンネルごとに明るさを下げて揃えます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhiteBalance {
+    scale: RGB8,
+}
+
+impl WhiteBalance {
+    /// 補正しない
+    pub const NONE: WhiteBalance = WhiteBalance::new(255, 255, 255);
+
+    /// 一般的な5050サイズのLEDの補正
+    pub const TYPICAL_5050: WhiteBalance = WhiteBalance::new(255, 176, 240);
+
+    /// チャンネルごとの明るさの割合(255で等倍)を指定します。
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self {
+            scale: RGB8 { r, g, b },
+        }
+    }
+
+    /// 白が`kelvin`の色温度になる補正を作ります。
+    ///
+    /// 1000Kから40000Kの範囲で、黒体放射の色の近似を使います。
+    pub const fn from_temperature(kelvin: u32) -> Self {
+        let kelvin = if kelvin < 1000 {
+            1000
+        } else if kelvin > 40000 {
+            40000
+        } else {
+            kelvin
+        };
+        let t = kelvin as f64 / 100.0;
+        let (r, g, b) = if t <= 66.0 {
+            let g = 99.4708025861 * ln(t) - 161.1195681661;
+            let b = if t <= 19.0 {
+                0.0
+            } else {
+                138.5177312231 * ln(t - 10.0) - 305.0447927307
+            };
+            (255.0, g, b)
+        } else {
+            let r = 329.698727446 * pow(t - 60.0, -0.1332047592);
+            let g = 288.1221695283 * pow(t - 60.0, -0.0755148492);
+            (r, g, 255.0)
+        };
+        Self::new(clamp_u8(r), clamp_u8(g), clamp_u8(b))
+    }
+
+    /// チャンネルごとの明るさの割合を返します。
+    pub const fn scale(&self) -> RGB8 {
+        self.scale
+    }
+}
+
+impl Correction for WhiteBalance {
+    fn correct(&self, color: RGB8) -> RGB8 {
+        let mul = |value: u8, scale: u8| ((value as u16 * (scale as u16 + 1)) >> 8) as u8;
+        RGB8::new(
+            mul(color.r, self.scale.r),
+            mul(color.g, self.scale.g),
+            mul(color.b, self.scale.b),
+        )
+    }
+}
+
+/// 3×3の色補正行列
+///
+/// 測色計で測った値に合わせたり、メーカーの違うストリップの色を揃えたりするのに使います。
+/// 係数は1/256単位の固定小数点で、256が1倍です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorMatrix {
+    /// `rows[出力のチャンネル][入力のチャンネル]`(R、G、Bの順)
+    rows: [[i16; 3]; 3],
+}
+
+impl ColorMatrix {
+    /// 補正しない
+    pub const IDENTITY: ColorMatrix = ColorMatrix::new([[256, 0, 0], [0, 256, 0], [0, 0, 256]]);
+
+    /// 1/256単位の係数から作ります。
+    pub const fn new(rows: [[i16; 3]; 3]) -> Self {
+        Self { rows }
+    }
+
+    /// 小数の係数から作ります。
+    pub const fn from_f32(rows: [[f32; 3]; 3]) -> Self {
+        let mut fixed = [[0; 3]; 3];
+        let mut i = 0;
+        while i < 9 {
+            let value = rows[i / 3][i % 3] * 256.0;
+            fixed[i / 3][i % 3] = if value < 0.0 {
+                (value - 0.5) as i16
+            } else {
+                (value + 0.5) as i16
+            };
+            i += 1;
+        }
+        Self { rows: fixed }
+    }
+}
+
+impl Correction for ColorMatrix {
+    fn correct(&self, color: RGB8) -> RGB8 {
+        let input = [color.r as i32, color.g as i32, color.b as i32];
+        let [r, g, b] = self.rows.map(|row| {
+            let sum: i32 = row.iter().zip(input).map(|(&m, v)| m as i32 * v).sum();
+            ((sum + 128) >> 8).clamp(0, 255) as u8
+        });
+        RGB8::new(r, g, b)
+    }
+}
+
+/// 書き込む色に補正をかけるドライバ
+pub struct Corrected<W, C>
+where
+    C: Correction,
+{
+    driver: W,
+    correction: C,
+}
+
+impl<W, C> Corrected<W, C>
+where
+    C: Correction,
+{
+    pub fn new(driver: W, correction: C) -> Self {
+        Self { driver, correction }
+    }
+
+    /// ドライバと補正を返します。
+    pub fn free(self) -> (W, C) {
+        (self.driver, self.correction)
+    }
+
+    /// 補正を返します。
+    pub fn correction(&self) -> &C {
+        &self.correction
+    }
+
+    /// 補正を変更できるように返します。
+    pub fn correction_mut(&mut self) -> &mut C {
+        &mut self.correction
+    }
+}
+
+impl<W, C> SmartLedsWrite for Corrected<W, C>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+    C: Correction,
+{
+    type Color = RGB8;
+    type Error = W::Error;
+    fn write<T, J>(&mut self, iterator: T) -> Result<(), W::Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<Self::Color>,
+    {
+        let correction = &self.correction;
+        self.driver.write(
+            iterator
+                .into_iter()
+                .map(|item| correction.correct(item.into())),
+        )
+    }
+}
+
+impl<S, C> Strip for Corrected<S, C>
+where
+    S: Strip,
+    C: Correction,
+{
+    fn write_word(&mut self, word: u32) -> bool {
+        self.driver.write_word(word)
+    }
+
+    fn wait_drained(&self) {
+        self.driver.wait_drained()
+    }
+
+    fn wait(&self) {
+        self.driver.wait()
+    }
+
+    fn vacancy(&mut self) -> Option<usize> {
+        self.driver.vacancy()
+    }
+
+    fn write_word_unchecked(&mut self, word: u32) {
+        self.driver.write_word_unchecked(word)
+    }
+
+    fn encode(&self, color: RGB8) -> u32 {
+        self.driver.encode(self.correction.correct(color))
+    }
+
+    /// RGBのチャンネルだけを補正し、白のチャンネルはそのまま送ります。
+    fn encode_rgbw(&self, color: RGBW<u8>) -> u32 {
+        let rgb = self
+            .correction
+            .correct(RGB8::new(color.r, color.g, color.b));
+        self.driver
+            .encode_rgbw(RGBW::new_alpha(rgb.r, rgb.g, rgb.b, color.a))
+    }
+
+    fn reset_micros(&self) -> u32 {
+        self.driver.reset_micros()
+    }
+}
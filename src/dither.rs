@@ -0,0 +1,107 @@
+//! 時間方向のディザリングです。
+//!
+//! 8bitの隣り合う2つの値をフレームごとに切り替え、平均で8bitより細かい明るさを表します。
+//! ゆっくりしたフェードで明るさが段になって見えるのを防げます。
+//! 効果を得るには、色が変わらなくても一定の間隔でフレームを送り続けてください。
+
+use smart_leds_trait::{RGB8, RGB16, SmartLedsWrite};
+
+use crate::Error;
+
+/// 1チャンネルの16bitの値を、前のフレームまでの誤差を足して8bitにします。
+///
+/// `floor`/256より暗い値は点滅が目立つので消灯します。
+fn dither_channel(value: u16, residual: &mut u8, floor: u8) -> u8 {
+    if value < floor as u16 {
+        return 0;
+    }
+    let high = (value >> 8) as u8;
+    let acc = (value & 0xff) + *residual as u16;
+    *residual = acc as u8;
+    if acc >= 0x100 {
+        high.saturating_add(1)
+    } else {
+        high
+    }
+}
+
+/// 16bitの色を時間方向にディザリングして送るドライバ
+///
+/// ピクセルごとに丸めの誤差を持つので、最大`N`ピクセルまで対応します。
+/// `N`個より長いフレームは[`Error::LengthMismatch`]になります。
+/// 長さの分かるイテレーターでは何も送らず、分からない場合は`N`個まで送ります。
+pub struct Dithered<W, const N: usize>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    driver: W,
+    /// ピクセルとチャンネルごとの丸めの誤差(1/256単位)
+    residuals: [[u8; 3]; N],
+    floor: u8,
+}
+
+impl<W, const N: usize> Dithered<W, N>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    pub fn new(driver: W) -> Self {
+        Self {
+            driver,
+            residuals: [[0; 3]; N],
+            floor: 0,
+        }
+    }
+
+    /// 1より暗い明るさを表すための低輝度モードにします。
+    ///
+    /// ピクセルごとに誤差の初期値をずらし、隣り合うピクセルが同じフレームで
+    /// 点灯しないようにするので、暗いところでストリップ全体が一斉に瞬きません。
+    /// さらに`floor`/256より暗い値は、点灯する間隔が長くちらついて見えるので消灯します。
+    /// 1/16程度(`floor`が16)が目安です。
+    pub fn set_low_brightness(&mut self, floor: u8) {
+        self.floor = floor;
+        for (index, residuals) in self.residuals.iter_mut().enumerate() {
+            // ビットを反転した番号で、近いピクセルほど離れた位相にします。
+            *residuals = [(index as u8).reverse_bits(); 3];
+        }
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> W {
+        self.driver
+    }
+}
+
+impl<W, const N: usize> SmartLedsWrite for Dithered<W, N>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+    W::Error: From<Error>,
+{
+    type Color = RGB16;
+    type Error = W::Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), W::Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let mut iter = iterator.into_iter();
+        if iter.size_hint().0 > N {
+            return Err(Error::LengthMismatch.into());
+        }
+        let floor = self.floor;
+        // 誤差を先に進めるので、`N`個を超えた分はイテレーターに残ります。
+        let pixels = self.residuals.iter_mut().zip(iter.by_ref());
+        self.driver.write(pixels.map(|([r, g, b], item)| {
+            let color: RGB16 = item.into();
+            RGB8::new(
+                dither_channel(color.r, r, floor),
+                dither_channel(color.g, g, floor),
+                dither_channel(color.b, b, floor),
+            )
+        }))?;
+        if iter.next().is_some() {
+            return Err(Error::LengthMismatch.into());
+        }
+        Ok(())
+    }
+}
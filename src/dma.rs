@@ -0,0 +1,300 @@
+//! DMAを使ってフレームバッファをPIOのTX FIFOへ送るドライバです。
+//!
+//! バッファの各ワードは`0xGGRRBB00`の形式でエンコードしておく必要があります。
+//! [`Ws2812Dma::encode_frame`]や[`encode_frame`](crate::encode_frame)で変換できます。
+
+use core::sync::atomic::AtomicU32;
+
+use embedded_dma::ReadBuffer;
+use rp235x_hal::{
+    dma::{Channel, ChannelIndex, single_buffer},
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pac,
+    pio::{PIO, PIOExt, StateMachineIndex, Tx, UninitStateMachine},
+};
+
+use smart_leds_trait::RGB8;
+
+use crate::{Error, Parts, Strip, Ws2812Direct};
+
+pub mod bytes;
+pub mod chained;
+pub mod continuous;
+pub mod double_buffer;
+pub mod irq;
+pub mod scatter;
+
+pub use bytes::Ws2812DmaBytes;
+pub use chained::FramesTransfer;
+pub use continuous::Ws2812DmaContinuous;
+pub use double_buffer::Ws2812DmaDouble;
+pub use irq::{DmaIrq, FrameDone};
+pub use scatter::Segment;
+
+/// DMAのペーシングタイマー
+///
+/// 選んだタイマーは他の用途に使わないでください。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacingTimer {
+    Timer0,
+    Timer1,
+    Timer2,
+    Timer3,
+}
+
+impl PacingTimer {
+    /// タイマーを1MHzで動くように設定し、そのDREQ番号を返します。
+    fn configure_1mhz(self, clock_freq: HertzU32) -> u8 {
+        let y = clock_freq.to_MHz();
+        assert!(
+            (1..=0xffff).contains(&y),
+            "System Clock must be within [1MHz, 65535MHz]."
+        );
+        let dma = regs();
+        // X/Y = 1/y で1MHzになります。
+        let bits = (1 << 16) | y;
+        match self {
+            PacingTimer::Timer0 => dma.timer0().write(|w| unsafe { w.bits(bits) }),
+            PacingTimer::Timer1 => dma.timer1().write(|w| unsafe { w.bits(bits) }),
+            PacingTimer::Timer2 => dma.timer2().write(|w| unsafe { w.bits(bits) }),
+            PacingTimer::Timer3 => dma.timer3().write(|w| unsafe { w.bits(bits) }),
+        };
+        59 + self as u8
+    }
+}
+
+/// ペーシングしない場合のDREQ番号
+const TREQ_UNPACED: u8 = 0x3f;
+
+/// DMAの完了後にFIFOとOSRに残りうるワード数
+pub(crate) const DRAIN_WORDS: u32 = 9;
+
+/// 待ち時間用のチャンネルの読み書き先
+static DUMMY: AtomicU32 = AtomicU32::new(0);
+
+/// チャンネルを直接設定するための値
+struct RawConfig {
+    read: u32,
+    write: u32,
+    count: u32,
+    incr_read: bool,
+    incr_write: bool,
+    treq: u8,
+    chain_to: u8,
+}
+
+fn regs() -> &'static pac::dma::RegisterBlock {
+    // Safety: 触るのは所有しているチャンネルのレジスタと、
+    // アトミックなトリガー/アボートのレジスタだけです。
+    unsafe { &*pac::DMA::ptr() }
+}
+
+/// チャンネルを設定します。起動はしません。
+fn configure(id: u8, config: RawConfig) {
+    let ch = regs().ch(id as usize);
+    ch.ch_read_addr().write(|w| unsafe { w.bits(config.read) });
+    ch.ch_write_addr()
+        .write(|w| unsafe { w.bits(config.write) });
+    ch.ch_trans_count()
+        .write(|w| unsafe { w.count().bits(config.count).mode().normal() });
+    ch.ch_al1_ctrl().write(|w| unsafe {
+        w.data_size().bits(2);
+        w.incr_read().bit(config.incr_read);
+        w.incr_write().bit(config.incr_write);
+        w.treq_sel().bits(config.treq);
+        w.chain_to().bits(config.chain_to);
+        w.en().set_bit()
+    });
+}
+
+/// `mask`のチャンネルを同時に起動します。
+fn trigger(mask: u16) {
+    regs()
+        .multi_chan_trigger()
+        .write(|w| unsafe { w.multi_chan_trigger().bits(mask) });
+}
+
+/// `mask`のチャンネルを止め、転送を中断します。
+fn abort(mask: u16) {
+    let dma = regs();
+    for id in 0..16 {
+        if mask & (1 << id) != 0 {
+            dma.ch(id).ch_al1_ctrl().modify(|_, w| w.en().clear_bit());
+        }
+    }
+    dma.chan_abort()
+        .write(|w| unsafe { w.chan_abort().bits(mask) });
+    while dma.chan_abort().read().bits() != 0 {}
+}
+
+/// DMAで送信するWS2812ドライバ
+///
+/// 送信中はドライバとバッファの所有権が[`DmaTransfer`]に移り、
+/// [`DmaTransfer::wait`]で返されます。
+/// フレーム間のリセット時間(50µs以上)は呼び出し側で確保してください。
+pub struct Ws2812Dma<P, SM, I, CH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    ch: Channel<CH>,
+}
+
+impl<P, SM, I, CH> Ws2812Dma<P, SM, I, CH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+{
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        ch: Channel<CH>,
+    ) -> Self {
+        let driver = Ws2812Direct::new(pin, pio, sm, clock_freq);
+        Self { driver, ch }
+    }
+
+    /// ドライバとDMAチャンネルに分解します。
+    pub fn free(self) -> (Ws2812Direct<P, SM, I>, Channel<CH>) {
+        (self.driver, self.ch)
+    }
+
+    /// `pixels`をドライバのデータの並びと明るさで`buffer`に変換し、書き込んだワード数を返します。
+    pub fn encode_frame(&self, pixels: &[RGB8], buffer: &mut [u32]) -> usize {
+        for (word, &pixel) in buffer.iter_mut().zip(pixels) {
+            *word = Strip::encode(&self.driver, pixel);
+        }
+        pixels.len().min(buffer.len())
+    }
+
+    /// 長さが分かる`pixels`を`buffer`に変換し、送るワード数を返します。
+    ///
+    /// [`Ws2812Direct::set_length`]でストリップの長さを設定している場合は、その長さまで消灯で埋めます。
+    /// フレームがストリップより長いか、送るワード数が`buffer`に入らない場合は、
+    /// 何も変換せずに[`Error::LengthMismatch`]を返します。
+    /// 返り値を[`Ws2812Dma::write_truncated`]に渡すと、転送の長さをフレームに合わせられます。
+    pub fn encode_exact<T>(&self, pixels: T, buffer: &mut [u32]) -> Result<usize, Error>
+    where
+        T: IntoIterator,
+        T::IntoIter: ExactSizeIterator,
+        T::Item: Into<RGB8>,
+    {
+        let pixels = pixels.into_iter();
+        let words = match self.driver.parts.length {
+            Some(length) if pixels.len() > length => return Err(Error::LengthMismatch),
+            Some(length) => length,
+            None => pixels.len(),
+        };
+        let Some(buffer) = buffer.get_mut(..words) else {
+            return Err(Error::LengthMismatch);
+        };
+        let colors = pixels
+            .map(Into::into)
+            .chain(core::iter::repeat(RGB8::default()));
+        for (word, color) in buffer.iter_mut().zip(colors) {
+            *word = Strip::encode(&self.driver, color);
+        }
+        Ok(words)
+    }
+
+    /// `buffer`の先頭の`len`ワードだけを送信します。
+    ///
+    /// 最大の長さで確保したバッファで、短いフレームを送るときに使います。
+    pub fn write_truncated<B>(
+        self,
+        buffer: B,
+        len: usize,
+    ) -> DmaTransfer<P, SM, I, CH, Truncated<B>>
+    where
+        B: ReadBuffer<Word = u32>,
+    {
+        self.write(Truncated { buffer, len })
+    }
+
+    /// `buffer`の送信を開始します。
+    ///
+    /// `&'static [u32]`や`cortex_m::singleton!`で確保した配列など、
+    /// [`ReadBuffer`]を実装した型なら何でも渡せます。
+    pub fn write<B>(self, buffer: B) -> DmaTransfer<P, SM, I, CH, B>
+    where
+        B: ReadBuffer<Word = u32>,
+    {
+        let Ws2812Direct { tx, parts } = self.driver;
+        let transfer = single_buffer::Config::new(self.ch, buffer, tx).start();
+        DmaTransfer { transfer, parts }
+    }
+}
+
+/// 先頭の`len`ワードだけを転送するバッファ
+pub struct Truncated<B> {
+    buffer: B,
+    len: usize,
+}
+
+impl<B> Truncated<B> {
+    /// 元のバッファを返します。
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+// Safety: 元のバッファの範囲を狭めるだけです。
+unsafe impl<B> ReadBuffer for Truncated<B>
+where
+    B: ReadBuffer<Word = u32>,
+{
+    type Word = u32;
+
+    unsafe fn read_buffer(&self) -> (*const u32, usize) {
+        // Safety: 呼び出し側の約束をそのまま元のバッファに引き継ぎます。
+        let (ptr, len) = unsafe { self.buffer.read_buffer() };
+        (ptr, len.min(self.len))
+    }
+}
+
+/// 送信中のDMA転送
+pub struct DmaTransfer<P, SM, I, CH, B>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    B: ReadBuffer<Word = u32>,
+{
+    transfer: single_buffer::Transfer<Channel<CH>, B, Tx<(P, SM)>>,
+    parts: Parts<P, SM, I>,
+}
+
+impl<P, SM, I, CH, B> DmaTransfer<P, SM, I, CH, B>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    B: ReadBuffer<Word = u32>,
+{
+    /// 全てのワードがFIFOに書き込まれたかを返します。
+    pub fn is_done(&self) -> bool {
+        self.transfer.is_done()
+    }
+
+    /// 転送の完了を待ち、ドライバとバッファを返します。
+    ///
+    /// この時点ではFIFOにデータが残っている可能性があります。
+    pub fn wait(self) -> (Ws2812Dma<P, SM, I, CH>, B) {
+        let (ch, buffer, tx) = self.transfer.wait();
+        let driver = Ws2812Direct {
+            tx,
+            parts: self.parts,
+        };
+        (Ws2812Dma { driver, ch }, buffer)
+    }
+}
@@ -0,0 +1,127 @@
+//! バイト列をそのままDMAで送るドライバです。
+//!
+//! FIFOへの8bitの書き込みは4バイト全てに複製されるので、
+//! ステートマシンを8bitごとにオートプルするように設定して1バイトずつ送ります。
+//! CPUでワードに詰め直す必要はありませんが、バイトは送信する順(GRB)に並べてください。
+//! [`RGB8`](smart_leds_trait::RGB8)はメモリ上でRGBの順なので、そのままでは使えません。
+//!
+//! `&'static [u8]`はそのままバッファとして渡せるので、フラッシュに置いたフレームを
+//! RAMへコピーせず、ピクセルごとのCPUの処理もなしに送れます。
+//! [`grb_bytes`]を使うと、`RGB8`の配列をコンパイル時にGRBのバイト列へ並べ替えられます。
+//!
+//! ```ignore
+//! static FRAME: [u8; 3 * 3] = grb_bytes(&[RED, GREEN, BLUE]);
+//! // 送信する順に並べたファイルをそのまま埋め込むこともできます。
+//! static LOGO: &[u8] = include_bytes!("logo.grb");
+//!
+//! let (dma, _) = dma.write(&FRAME[..]).wait();
+//! let (dma, _) = dma.write(LOGO).wait();
+//! ```
+
+use embedded_dma::ReadBuffer;
+use rp235x_hal::{
+    dma::{Byte, Channel, ChannelIndex, Word, single_buffer},
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, StateMachineIndex, Tx, UninitStateMachine},
+};
+use smart_leds_trait::RGB8;
+
+use crate::{Parts, Timing, Ws2812Direct};
+
+/// `pixels`を送信する順(GRB)のバイト列に並べ替えます。
+///
+/// `const`や`static`の初期化に使うと、並べ替えたフレームをフラッシュに置けます。
+/// `M`は`N`の3倍にしてください。
+pub const fn grb_bytes<const N: usize, const M: usize>(pixels: &[RGB8; N]) -> [u8; M] {
+    assert!(M == N * 3, "M must be 3 times N.");
+    let mut bytes = [0; M];
+    let mut i = 0;
+    while i < N {
+        bytes[i * 3] = pixels[i].g;
+        bytes[i * 3 + 1] = pixels[i].r;
+        bytes[i * 3 + 2] = pixels[i].b;
+        i += 1;
+    }
+    bytes
+}
+
+/// バイト列をDMAで送信するWS2812ドライバ
+pub struct Ws2812DmaBytes<P, SM, I, CH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    ch: Channel<CH>,
+}
+
+impl<P, SM, I, CH> Ws2812DmaBytes<P, SM, I, CH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+{
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        ch: Channel<CH>,
+    ) -> Self {
+        let driver = Ws2812Direct::with_pull_threshold(pin, pio, sm, clock_freq, Timing::WS2812, 8);
+        Self { driver, ch }
+    }
+
+    /// `buffer`の送信を開始します。
+    pub fn write<B>(self, buffer: B) -> BytesTransfer<P, SM, I, CH, B>
+    where
+        B: ReadBuffer<Word = u8>,
+    {
+        let Ws2812Direct { tx, parts } = self.driver;
+        let transfer = single_buffer::Config::new(self.ch, buffer, tx.transfer_size(Byte)).start();
+        BytesTransfer { transfer, parts }
+    }
+}
+
+/// 送信中のバイト列のDMA転送
+pub struct BytesTransfer<P, SM, I, CH, B>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    B: ReadBuffer<Word = u8>,
+{
+    transfer: single_buffer::Transfer<Channel<CH>, B, Tx<(P, SM), Byte>>,
+    parts: Parts<P, SM, I>,
+}
+
+impl<P, SM, I, CH, B> BytesTransfer<P, SM, I, CH, B>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    B: ReadBuffer<Word = u8>,
+{
+    /// 全てのバイトがFIFOに書き込まれたかを返します。
+    pub fn is_done(&self) -> bool {
+        self.transfer.is_done()
+    }
+
+    /// 転送の完了を待ち、ドライバとバッファを返します。
+    ///
+    /// この時点ではFIFOにデータが残っている可能性があります。
+    pub fn wait(self) -> (Ws2812DmaBytes<P, SM, I, CH>, B) {
+        let (ch, buffer, tx) = self.transfer.wait();
+        let driver = Ws2812Direct {
+            tx: tx.transfer_size(Word),
+            parts: self.parts,
+        };
+        (Ws2812DmaBytes { driver, ch }, buffer)
+    }
+}
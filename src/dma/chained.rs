@@ -0,0 +1,156 @@
+//! 複数のフレームをリセット時間だけ空けて隙間なく送り続けるモードです。
+//!
+//! データ用のチャンネルがフレームを送り終わると、ペーシングタイマーで刻まれる
+//! 待ち時間用のチャンネルに連鎖し、制御用のチャンネルが[`Segment`]のリストから
+//! 次のフレームを読んでデータ用のチャンネルを再起動します。
+//! CPUを使わずにリセット時間が明けた瞬間に次のフレームを送り始めるので、
+//! POVや動画のように最大のフレームレートで送り続けたい場合に使えます。
+
+use rp235x_hal::{
+    dma::{Channel, ChannelIndex},
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIOExt, StateMachineIndex},
+};
+
+use super::{
+    DRAIN_WORDS, DUMMY, PacingTimer, RawConfig, Segment, TREQ_UNPACED, Ws2812Dma, configure, regs,
+    trigger,
+};
+
+impl<P, SM, I, CH> Ws2812Dma<P, SM, I, CH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+{
+    /// `frames`の各区間を1つのフレームとして、リセット時間を挟みながら順に送信します。
+    ///
+    /// `frames`の最後は[`Segment::END`]にしてください。
+    pub fn write_frames<GCH, CCH>(
+        self,
+        gap_ch: Channel<GCH>,
+        ctrl_ch: Channel<CCH>,
+        timer: PacingTimer,
+        clock_freq: HertzU32,
+        frames: &'static [Segment],
+    ) -> FramesTransfer<P, SM, I, CH, GCH, CCH>
+    where
+        GCH: ChannelIndex,
+        CCH: ChannelIndex,
+    {
+        assert_eq!(
+            frames.last(),
+            Some(&Segment::END),
+            "frames must end with Segment::END"
+        );
+        let (data, gap, ctrl) = (CH::id(), GCH::id(), CCH::id());
+        let dummy = DUMMY.as_ptr() as u32;
+
+        configure(
+            data,
+            RawConfig {
+                read: 0,
+                write: self.driver.tx.fifo_address() as u32,
+                count: 0,
+                incr_read: true,
+                incr_write: false,
+                treq: self.driver.tx.dreq_value(),
+                chain_to: gap,
+            },
+        );
+        configure(
+            gap,
+            RawConfig {
+                read: dummy,
+                write: dummy,
+                count: self.driver.reset_micros() + DRAIN_WORDS * self.driver.word_micros(),
+                incr_read: false,
+                incr_write: false,
+                treq: timer.configure_1mhz(clock_freq),
+                chain_to: ctrl,
+            },
+        );
+        configure(
+            ctrl,
+            RawConfig {
+                read: frames.as_ptr() as u32,
+                write: regs().ch(data as usize).ch_al3_trans_count().as_ptr() as u32,
+                count: 2,
+                incr_read: true,
+                incr_write: true,
+                treq: TREQ_UNPACED,
+                chain_to: ctrl,
+            },
+        );
+        // 書き込み先をAL3_TRANS_COUNTとAL3_READ_ADDR_TRIGの8バイトで折り返します。
+        regs()
+            .ch(ctrl as usize)
+            .ch_al1_ctrl()
+            .modify(|_, w| unsafe { w.ring_sel().set_bit().ring_size().bits(3) });
+        trigger(1 << ctrl);
+
+        FramesTransfer {
+            dma: self,
+            gap_ch,
+            ctrl_ch,
+            frames,
+        }
+    }
+}
+
+/// 送信中のフレームの列
+pub struct FramesTransfer<P, SM, I, CH, GCH, CCH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    GCH: ChannelIndex,
+    CCH: ChannelIndex,
+{
+    dma: Ws2812Dma<P, SM, I, CH>,
+    gap_ch: Channel<GCH>,
+    ctrl_ch: Channel<CCH>,
+    frames: &'static [Segment],
+}
+
+impl<P, SM, I, CH, GCH, CCH> FramesTransfer<P, SM, I, CH, GCH, CCH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    GCH: ChannelIndex,
+    CCH: ChannelIndex,
+{
+    /// 全てのフレームを送り、最後のフレームのリセット時間が経ったかを返します。
+    pub fn is_done(&self) -> bool {
+        let end = self.frames.as_ptr_range().end as u32;
+        let dma = regs();
+        let ctrl = dma.ch(CCH::id() as usize);
+        ctrl.ch_read_addr().read().bits() == end
+            && [CH::id(), GCH::id(), CCH::id()].iter().all(|&id| {
+                !dma.ch(id as usize)
+                    .ch_ctrl_trig()
+                    .read()
+                    .busy()
+                    .bit_is_set()
+            })
+    }
+
+    /// 送信の完了を待ち、ドライバ、チャンネル、フレームのリストを返します。
+    #[allow(clippy::type_complexity)]
+    pub fn wait(
+        self,
+    ) -> (
+        Ws2812Dma<P, SM, I, CH>,
+        Channel<GCH>,
+        Channel<CCH>,
+        &'static [Segment],
+    ) {
+        while !self.is_done() {}
+        (self.dma, self.gap_ch, self.ctrl_ch, self.frames)
+    }
+}
@@ -0,0 +1,147 @@
+//! フレームバッファをCPUを使わずに一定のフレームレートで送り続けるモードです。
+//!
+//! 3つのDMAチャンネルを使います。
+//! データ用のチャンネルがバッファを送り終わると、ペーシングタイマーで刻まれる
+//! 待ち時間用のチャンネルに連鎖し、最後に制御用のチャンネルがデータ用の
+//! チャンネルの読み出しアドレスを書き戻して再起動します。
+
+use core::{
+    slice,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use embedded_dma::{ReadBuffer, WriteBuffer};
+use rp235x_hal::{
+    dma::{Channel, ChannelIndex},
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIOExt, StateMachineIndex},
+};
+
+use super::{
+    DRAIN_WORDS, DUMMY, PacingTimer, RawConfig, TREQ_UNPACED, Ws2812Dma, abort, configure, regs,
+    trigger,
+};
+
+/// 制御用のチャンネルが読み出すバッファのアドレス(チャンネル番号ごと)
+static RELOAD: [AtomicU32; 16] = [const { AtomicU32::new(0) }; 16];
+
+/// バッファを送り続けるDMAドライバ
+///
+/// バッファは[`Ws2812DmaContinuous::buffer_mut`]で直接書き換えてください。
+/// 送信中に書き換えると、そのフレームには新旧のデータが混ざることがあります。
+pub struct Ws2812DmaContinuous<P, SM, I, CH, GCH, CCH, B>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    GCH: ChannelIndex,
+    CCH: ChannelIndex,
+    B: ReadBuffer<Word = u32> + WriteBuffer<Word = u32>,
+{
+    dma: Ws2812Dma<P, SM, I, CH>,
+    gap_ch: Channel<GCH>,
+    ctrl_ch: Channel<CCH>,
+    buffer: B,
+}
+
+impl<P, SM, I, CH, GCH, CCH, B> Ws2812DmaContinuous<P, SM, I, CH, GCH, CCH, B>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    GCH: ChannelIndex,
+    CCH: ChannelIndex,
+    B: ReadBuffer<Word = u32> + WriteBuffer<Word = u32>,
+{
+    /// `fps`で`buffer`の送信を始めます。
+    ///
+    /// フレームの送信時間とリセット時間を足した時間より短い周期は指定できず、
+    /// その場合は可能な最大のフレームレートになります。
+    pub fn new(
+        dma: Ws2812Dma<P, SM, I, CH>,
+        gap_ch: Channel<GCH>,
+        ctrl_ch: Channel<CCH>,
+        timer: PacingTimer,
+        clock_freq: HertzU32,
+        fps: u32,
+        buffer: B,
+    ) -> Self {
+        // Safety: バッファは`stop`で転送を止めるまで手放しません。
+        let (ptr, len) = unsafe { buffer.read_buffer() };
+        let (ptr, len) = (ptr as u32, len as u32);
+        let period = 1_000_000 / fps.max(1);
+        let word_micros = dma.driver.word_micros();
+        let gap = period
+            .saturating_sub(len * word_micros)
+            .max(dma.driver.reset_micros() + DRAIN_WORDS * word_micros);
+
+        let (data, gap_id, ctrl) = (CH::id(), GCH::id(), CCH::id());
+        RELOAD[ctrl as usize].store(ptr, Ordering::Relaxed);
+        let dummy = DUMMY.as_ptr() as u32;
+
+        configure(
+            data,
+            RawConfig {
+                read: ptr,
+                write: dma.driver.tx.fifo_address() as u32,
+                count: len,
+                incr_read: true,
+                incr_write: false,
+                treq: dma.driver.tx.dreq_value(),
+                chain_to: gap_id,
+            },
+        );
+        configure(
+            gap_id,
+            RawConfig {
+                read: dummy,
+                write: dummy,
+                count: gap,
+                incr_read: false,
+                incr_write: false,
+                treq: timer.configure_1mhz(clock_freq),
+                chain_to: ctrl,
+            },
+        );
+        configure(
+            ctrl,
+            RawConfig {
+                read: RELOAD[ctrl as usize].as_ptr() as u32,
+                write: regs().ch(data as usize).ch_al3_read_addr_trig().as_ptr() as u32,
+                count: 1,
+                incr_read: false,
+                incr_write: false,
+                treq: TREQ_UNPACED,
+                chain_to: ctrl,
+            },
+        );
+        trigger(1 << data);
+
+        Self {
+            dma,
+            gap_ch,
+            ctrl_ch,
+            buffer,
+        }
+    }
+
+    /// 送信し続けているバッファを返します。
+    pub fn buffer_mut(&mut self) -> &mut [u32] {
+        // Safety: DMAは読み出すだけなので、書き換えても転送は壊れません。
+        unsafe {
+            let (ptr, len) = self.buffer.write_buffer();
+            slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    /// 送信を止め、ドライバ、チャンネル、バッファを返します。
+    #[allow(clippy::type_complexity)]
+    pub fn stop(self) -> (Ws2812Dma<P, SM, I, CH>, Channel<GCH>, Channel<CCH>, B) {
+        abort((1 << CH::id()) | (1 << GCH::id()) | (1 << CCH::id()));
+        self.dma.driver.wait_drained();
+        (self.dma, self.gap_ch, self.ctrl_ch, self.buffer)
+    }
+}
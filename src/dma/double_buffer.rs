@@ -0,0 +1,107 @@
+//! 2枚のバッファを交互に送信するダブルバッファリングです。
+//!
+//! 片方のバッファをDMAで送信している間に、もう片方へ次のフレームを描画できます。
+
+use core::{mem, slice};
+
+use cortex_m::prelude::_embedded_hal_timer_CountDown;
+use embedded_dma::{ReadBuffer, WriteBuffer};
+use rp235x_hal::{
+    dma::ChannelIndex,
+    fugit::ExtU32,
+    gpio::AnyPin,
+    pio::{PIOExt, StateMachineIndex},
+    timer::{CountDown, TimerDevice},
+};
+
+use super::{DmaTransfer, Ws2812Dma};
+
+enum Front<P, SM, I, CH, B>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    B: ReadBuffer<Word = u32> + WriteBuffer<Word = u32>,
+{
+    Idle(Ws2812Dma<P, SM, I, CH>, B),
+    Busy(DmaTransfer<P, SM, I, CH, B>),
+}
+
+/// ダブルバッファリングするDMAドライバ
+pub struct Ws2812DmaDouble<'timer, D, P, SM, I, CH, B>
+where
+    D: TimerDevice,
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    B: ReadBuffer<Word = u32> + WriteBuffer<Word = u32>,
+{
+    cd: CountDown<'timer, D>,
+    front: Option<Front<P, SM, I, CH, B>>,
+    back: B,
+}
+
+impl<'timer, D, P, SM, I, CH, B> Ws2812DmaDouble<'timer, D, P, SM, I, CH, B>
+where
+    D: TimerDevice,
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    B: ReadBuffer<Word = u32> + WriteBuffer<Word = u32>,
+{
+    /// `front`と`back`は同じ長さにしてください。
+    pub fn new(dma: Ws2812Dma<P, SM, I, CH>, front: B, back: B, cd: CountDown<'timer, D>) -> Self {
+        Self {
+            cd,
+            front: Some(Front::Idle(dma, front)),
+            back,
+        }
+    }
+
+    /// 次に送信するバッファを返します。
+    pub fn back_mut(&mut self) -> &mut [u32] {
+        // Safety: 描画先のバッファはDMAに渡していません。
+        unsafe {
+            let (ptr, len) = self.back.write_buffer();
+            slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    /// 送信中のフレームが終わるのを待ち、描画済みのバッファの送信を開始します。
+    ///
+    /// 送信していたバッファが新しい描画先になります。
+    pub fn swap(&mut self) {
+        let (dma, front) = self.wait_front();
+        let back = mem::replace(&mut self.back, front);
+        self.front = Some(Front::Busy(dma.write(back)));
+    }
+
+    /// フレームを送信中かを返します。
+    pub fn is_busy(&self) -> bool {
+        matches!(&self.front, Some(Front::Busy(transfer)) if !transfer.is_done())
+    }
+
+    /// ドライバとバッファを返します。
+    pub fn free(mut self) -> (Ws2812Dma<P, SM, I, CH>, B, B, CountDown<'timer, D>) {
+        let (dma, front) = self.wait_front();
+        (dma, front, self.back, self.cd)
+    }
+
+    fn wait_front(&mut self) -> (Ws2812Dma<P, SM, I, CH>, B) {
+        match self.front.take().unwrap() {
+            Front::Idle(dma, front) => (dma, front),
+            Front::Busy(transfer) => {
+                let (dma, front) = transfer.wait();
+                dma.driver.wait_drained();
+
+                self.cd.start(dma.driver.reset_micros().micros());
+                let _ = nb::block!(self.cd.wait());
+
+                (dma, front)
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! DMAの完了割り込みでフレームの送信完了を知らせる仕組みです。
+//!
+//! [`Ws2812Dma::enable_irq`]で割り込みを有効にし、`DMA_IRQ_0`/`DMA_IRQ_1`の
+//! ハンドラから[`FrameDone::on_interrupt`]を呼んでください。
+//!
+//! ```ignore
+//! static DONE: FrameDone<CH0> = FrameDone::new();
+//!
+//! #[interrupt]
+//! fn DMA_IRQ_0() {
+//!     DONE.on_interrupt(DmaIrq::Irq0);
+//! }
+//! ```
+
+use core::{
+    cell::Cell,
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use critical_section::Mutex;
+use rp235x_hal::{
+    dma::{ChannelIndex, SingleChannel},
+    gpio::AnyPin,
+    pio::{PIOExt, StateMachineIndex},
+};
+
+use super::{Ws2812Dma, regs};
+
+/// DMAの割り込み線
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaIrq {
+    Irq0,
+    Irq1,
+}
+
+type Callback = Mutex<Cell<Option<fn()>>>;
+
+/// フレームの送信完了フラグ
+pub struct FrameDone<CH: ChannelIndex> {
+    done: AtomicBool,
+    callback: Callback,
+    _ch: PhantomData<fn() -> CH>,
+}
+
+impl<CH: ChannelIndex> Default for FrameDone<CH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<CH: ChannelIndex> FrameDone<CH> {
+    pub const fn new() -> Self {
+        Self {
+            done: AtomicBool::new(false),
+            callback: Mutex::new(Cell::new(None)),
+            _ch: PhantomData,
+        }
+    }
+
+    /// 送信完了時に割り込みハンドラの中で呼ばれる関数を登録します。
+    pub fn set_callback(&self, callback: Option<fn()>) {
+        critical_section::with(|cs| self.callback.borrow(cs).set(callback));
+    }
+
+    /// 割り込みハンドラから呼んでください。
+    ///
+    /// このチャンネルの割り込みだった場合は割り込みを解除してフラグを立て、`true`を返します。
+    pub fn on_interrupt(&self, irq: DmaIrq) -> bool {
+        let dma = regs();
+        let mask = 1 << CH::id();
+        let pending = match irq {
+            DmaIrq::Irq0 => dma.ints0().read().bits() & mask != 0,
+            DmaIrq::Irq1 => dma.ints1().read().bits() & mask != 0,
+        };
+        if !pending {
+            return false;
+        }
+        // Safety: 書き込んだビットのチャンネルの割り込みだけが解除されます。
+        match irq {
+            DmaIrq::Irq0 => dma.ints0().write(|w| unsafe { w.bits(mask) }),
+            DmaIrq::Irq1 => dma.ints1().write(|w| unsafe { w.bits(mask) }),
+        };
+        self.done.store(true, Ordering::Release);
+        if let Some(callback) = critical_section::with(|cs| self.callback.borrow(cs).get()) {
+            callback();
+        }
+        true
+    }
+
+    /// 送信が完了しているかを返します。
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// 送信完了フラグを取り出し、下ろします。
+    pub fn take(&self) -> bool {
+        self.done.swap(false, Ordering::AcqRel)
+    }
+}
+
+impl<P, SM, I, CH> Ws2812Dma<P, SM, I, CH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+{
+    /// 転送完了時に`irq`の割り込みを発生させます。
+    pub fn enable_irq(&mut self, irq: DmaIrq) {
+        match irq {
+            DmaIrq::Irq0 => self.ch.enable_irq0(),
+            DmaIrq::Irq1 => self.ch.enable_irq1(),
+        }
+    }
+
+    /// `irq`の割り込みを無効にします。
+    pub fn disable_irq(&mut self, irq: DmaIrq) {
+        match irq {
+            DmaIrq::Irq0 => self.ch.disable_irq0(),
+            DmaIrq::Irq1 => self.ch.disable_irq1(),
+        }
+    }
+}
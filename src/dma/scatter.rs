@@ -0,0 +1,144 @@
+//! 連続していない複数のバッファを1つのフレームとして送るスキャッター/ギャザー転送です。
+//!
+//! 制御用のチャンネルが[`Segment`]のリストを読み、データ用のチャンネルの
+//! 転送数と読み出しアドレスを書き換えて再起動します。
+//! 読み出しアドレスが0の[`Segment::END`]でデータ用のチャンネルは起動されず、転送が終わります。
+
+use rp235x_hal::{
+    dma::{Channel, ChannelIndex},
+    gpio::AnyPin,
+    pio::{PIOExt, StateMachineIndex},
+};
+
+use super::{RawConfig, TREQ_UNPACED, Ws2812Dma, configure, regs, trigger};
+
+/// 送信する1区間
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    count: u32,
+    read_addr: u32,
+}
+
+impl Segment {
+    /// リストの終端
+    pub const END: Segment = Segment {
+        count: 0,
+        read_addr: 0,
+    };
+
+    pub fn new(words: &'static [u32]) -> Self {
+        Self {
+            count: words.len() as u32,
+            read_addr: words.as_ptr() as u32,
+        }
+    }
+}
+
+impl<P, SM, I, CH> Ws2812Dma<P, SM, I, CH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+{
+    /// `segments`を順に1つのフレームとして送信します。
+    ///
+    /// `segments`の最後は[`Segment::END`]にしてください。
+    pub fn write_segments<CCH>(
+        self,
+        ctrl_ch: Channel<CCH>,
+        segments: &'static [Segment],
+    ) -> SegmentTransfer<P, SM, I, CH, CCH>
+    where
+        CCH: ChannelIndex,
+    {
+        assert_eq!(
+            segments.last(),
+            Some(&Segment::END),
+            "segments must end with Segment::END"
+        );
+        let (data, ctrl) = (CH::id(), CCH::id());
+        let data_regs = regs().ch(data as usize);
+
+        configure(
+            data,
+            RawConfig {
+                read: 0,
+                write: self.driver.tx.fifo_address() as u32,
+                count: 0,
+                incr_read: true,
+                incr_write: false,
+                treq: self.driver.tx.dreq_value(),
+                chain_to: ctrl,
+            },
+        );
+        configure(
+            ctrl,
+            RawConfig {
+                read: segments.as_ptr() as u32,
+                write: data_regs.ch_al3_trans_count().as_ptr() as u32,
+                count: 2,
+                incr_read: true,
+                incr_write: true,
+                treq: TREQ_UNPACED,
+                chain_to: ctrl,
+            },
+        );
+        // 書き込み先をAL3_TRANS_COUNTとAL3_READ_ADDR_TRIGの8バイトで折り返します。
+        regs()
+            .ch(ctrl as usize)
+            .ch_al1_ctrl()
+            .modify(|_, w| unsafe { w.ring_sel().set_bit().ring_size().bits(3) });
+        trigger(1 << ctrl);
+
+        SegmentTransfer {
+            dma: self,
+            ctrl_ch,
+            segments,
+        }
+    }
+}
+
+/// 送信中のスキャッター/ギャザー転送
+pub struct SegmentTransfer<P, SM, I, CH, CCH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    CCH: ChannelIndex,
+{
+    dma: Ws2812Dma<P, SM, I, CH>,
+    ctrl_ch: Channel<CCH>,
+    segments: &'static [Segment],
+}
+
+impl<P, SM, I, CH, CCH> SegmentTransfer<P, SM, I, CH, CCH>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: ChannelIndex,
+    CCH: ChannelIndex,
+{
+    /// 全ての区間がFIFOに書き込まれたかを返します。
+    pub fn is_done(&self) -> bool {
+        let end = self.segments.as_ptr_range().end as u32;
+        let dma = regs();
+        let data = dma.ch(CH::id() as usize);
+        let ctrl = dma.ch(CCH::id() as usize);
+        ctrl.ch_read_addr().read().bits() == end
+            && !ctrl.ch_ctrl_trig().read().busy().bit_is_set()
+            && !data.ch_ctrl_trig().read().busy().bit_is_set()
+    }
+
+    /// 転送の完了を待ち、ドライバ、制御用のチャンネル、区間のリストを返します。
+    ///
+    /// この時点ではFIFOにデータが残っている可能性があります。
+    #[allow(clippy::type_complexity)]
+    pub fn wait(self) -> (Ws2812Dma<P, SM, I, CH>, Channel<CCH>, &'static [Segment]) {
+        while !self.is_done() {}
+        (self.dma, self.ctrl_ch, self.segments)
+    }
+}
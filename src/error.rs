@@ -0,0 +1,15 @@
+//! ドライバのエラーです。
+
+/// 書き込みのエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// フレームが設定したストリップの長さより長い
+    ///
+    /// イテレーターの長さが分かる場合は何も送られていません。
+    /// 分からない場合は、ストリップの長さまでは送られています。
+    LengthMismatch,
+    /// ストリップが長すぎて、指定したフレームレートで更新できない
+    FrameRateTooHigh,
+    /// 前のフレームを送っている途中か、キューが一杯で受け付けられない
+    Busy,
+}
@@ -0,0 +1,47 @@
+//! 時間をかけて値を変えるフェードです。
+
+use rp235x_hal::{fugit::MicrosDurationU32, timer::Instant};
+
+/// `from`から`to`へ`duration`かけて直線的に変わる値
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fade {
+    from: u8,
+    to: u8,
+    start: Instant,
+    duration: MicrosDurationU32,
+}
+
+impl Fade {
+    pub fn new(from: u8, to: u8, start: Instant, duration: MicrosDurationU32) -> Self {
+        Self {
+            from,
+            to,
+            start,
+            duration,
+        }
+    }
+
+    /// 変わり終える値を返します。
+    pub fn target(&self) -> u8 {
+        self.to
+    }
+
+    /// `now`の時点の値を返します。
+    pub fn level(&self, now: Instant) -> u8 {
+        let total = self.duration.to_micros() as u64;
+        let elapsed = now
+            .checked_duration_since(self.start)
+            .map_or(0, |elapsed| elapsed.to_micros());
+        if elapsed >= total {
+            return self.to;
+        }
+        let (from, to) = (self.from as i64, self.to as i64);
+        (from + (to - from) * elapsed as i64 / total as i64) as u8
+    }
+
+    /// `now`の時点で変わり終えているかを返します。
+    pub fn is_done(&self, now: Instant) -> bool {
+        now.checked_duration_since(self.start)
+            .is_some_and(|elapsed| elapsed.to_micros() >= self.duration.to_micros() as u64)
+    }
+}
@@ -0,0 +1,251 @@
+//! 同期版と非同期版のドライバで共有する、フレームをFIFOに送り込む処理です。
+//!
+//! どのドライバもこの[`Feeder`]にFIFOを埋めさせ、FIFOが一杯のときの待ち方だけを変えます。
+
+use core::iter::RepeatN;
+
+use smart_leds_trait::{RGB8, RGBW};
+
+use crate::Strip;
+
+/// イテレーターの要素をFIFOに書き込むワードに変換する方法
+pub(crate) trait Encoding: Copy {
+    /// 変換する色の型
+    type Color: Copy + Default;
+
+    fn encode<S>(&self, strip: &S, color: Self::Color) -> Words
+    where
+        S: Strip + ?Sized;
+}
+
+/// RGBの色として変換します。
+#[derive(Clone, Copy)]
+pub(crate) struct Rgb;
+
+impl Encoding for Rgb {
+    type Color = RGB8;
+
+    fn encode<S>(&self, strip: &S, color: RGB8) -> Words
+    where
+        S: Strip + ?Sized,
+    {
+        strip.encode(color).into()
+    }
+}
+
+/// 白のチャンネルを含む色として変換します。
+#[derive(Clone, Copy)]
+pub(crate) struct Rgbw;
+
+impl Encoding for Rgbw {
+    type Color = RGBW<u8>;
+
+    fn encode<S>(&self, strip: &S, color: RGBW<u8>) -> Words
+    where
+        S: Strip + ?Sized,
+    {
+        strip.encode_rgbw(color).into()
+    }
+}
+
+/// 書き込み途中のフレームと、その後ろに送る消灯のデータ
+pub(crate) struct Frame<T, E: Encoding = Rgb> {
+    pub(crate) feeder: Feeder<T, E>,
+    /// 書き込めるピクセル数の上限
+    pub(crate) limit: usize,
+    /// フレームを書き終えてから送る、ストリップの長さまでの消灯のデータ
+    pub(crate) black: Option<Feeder<RepeatN<E::Color>, E>>,
+    /// 最初のワードを書き込んだ
+    pub(crate) started: bool,
+}
+
+impl<T, E> Frame<T, E>
+where
+    T: Iterator,
+    T::Item: Into<E::Color>,
+    E: Encoding,
+{
+    pub(crate) fn new(feeder: Feeder<T, E>, limit: usize) -> Self {
+        Self {
+            feeder,
+            limit,
+            black: None,
+            started: false,
+        }
+    }
+}
+
+/// 書き込み途中のイテレーター
+pub(crate) struct Feeder<T, E = Rgb> {
+    iter: T,
+    /// 書き込み途中のピクセルの残りのワード
+    pending: Words,
+    /// FIFOに書き込み終えたピクセル数
+    written: usize,
+    encoding: E,
+}
+
+impl<T> Feeder<T>
+where
+    T: Iterator,
+    T::Item: Into<RGB8>,
+{
+    pub(crate) fn new(iter: T) -> Self {
+        Self::with_encoding(iter, Rgb)
+    }
+}
+
+impl<T, E> Feeder<T, E>
+where
+    T: Iterator,
+    T::Item: Into<E::Color>,
+    E: Encoding,
+{
+    /// 要素を`encoding`で変換するイテレーターから作ります。
+    pub(crate) fn with_encoding(iter: T, encoding: E) -> Self {
+        Self {
+            iter,
+            pending: Words::default(),
+            written: 0,
+            encoding,
+        }
+    }
+
+    /// 同じ変換で、`count`個の消灯のデータを送るフィーダーを作ります。
+    pub(crate) fn black(&self, count: usize) -> Feeder<RepeatN<E::Color>, E> {
+        Feeder::with_encoding(
+            core::iter::repeat_n(E::Color::default(), count),
+            self.encoding,
+        )
+    }
+
+    /// 元のイテレーターを返します。
+    pub(crate) fn iter_mut(&mut self) -> &mut T {
+        &mut self.iter
+    }
+
+    /// 途中までしか書き込めていないピクセルがあるかを返します。
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// FIFOに書き込み終えたピクセル数を返します。
+    pub(crate) fn written(&self) -> usize {
+        self.written
+    }
+
+    /// まだ書き込んでいないピクセルが残っているかを返します。
+    ///
+    /// 残っている場合は1つ読み進めます。
+    pub(crate) fn has_more(&mut self) -> bool {
+        self.has_pending() || self.iter.next().is_some()
+    }
+
+    /// FIFOが一杯になるか、書き込んだピクセル数が`limit`に達するまで書き込みます。
+    ///
+    /// FIFOの空きを調べられるストリップには、空きの数だけまとめて書き込みます。
+    /// フレームを全て書き込んだら`true`を返します。
+    pub(crate) fn feed<S>(&mut self, strip: &mut S, limit: usize) -> bool
+    where
+        S: Strip + ?Sized,
+    {
+        while self.written < limit || self.has_pending() {
+            let burst = match strip.vacancy() {
+                Some(0) => return false,
+                Some(room) => room,
+                None => return self.feed_checked(strip, limit),
+            };
+            for _ in 0..burst {
+                let Some(word) = self.next_word(strip, limit) else {
+                    return self.written < limit;
+                };
+                strip.write_word_unchecked(word);
+                self.advance();
+            }
+        }
+        false
+    }
+
+    /// 1ワードずつFIFOが一杯かを確かめながら書き込みます。
+    fn feed_checked<S>(&mut self, strip: &mut S, limit: usize) -> bool
+    where
+        S: Strip + ?Sized,
+    {
+        loop {
+            let Some(word) = self.next_word(strip, limit) else {
+                return self.written < limit;
+            };
+            if !strip.write_word(word) {
+                return false;
+            }
+            self.advance();
+        }
+    }
+
+    /// 次に書き込むワードを返します。
+    ///
+    /// `limit`ピクセル書き込み終えているか、フレームが終わった場合は`None`を返します。
+    fn next_word<S>(&mut self, strip: &S, limit: usize) -> Option<u32>
+    where
+        S: Strip + ?Sized,
+    {
+        if self.pending.is_empty() {
+            if self.written >= limit {
+                return None;
+            }
+            let item = self.iter.next()?;
+            self.pending = self.encoding.encode(strip, item.into());
+        }
+        self.pending.front()
+    }
+
+    /// `next_word`のワードを書き込んだので、次のワードに進みます。
+    fn advance(&mut self) {
+        self.pending.pop();
+        if self.pending.is_empty() {
+            self.written += 1;
+        }
+    }
+}
+
+/// 1ピクセル分のワード
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Words {
+    words: [u32; Words::MAX],
+    start: u8,
+    end: u8,
+}
+
+impl Words {
+    /// 1ピクセルの最大のワード数
+    pub(crate) const MAX: usize = 5;
+
+    /// `words`を順に書き込みます。`Words::MAX`個までです。
+    pub(crate) fn new(words: &[u32]) -> Self {
+        let mut buffer = [0; Self::MAX];
+        buffer[..words.len()].copy_from_slice(words);
+        Self {
+            words: buffer,
+            start: 0,
+            end: words.len() as u8,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    fn front(&self) -> Option<u32> {
+        (!self.is_empty()).then(|| self.words[self.start as usize])
+    }
+
+    fn pop(&mut self) {
+        self.start += 1;
+    }
+}
+
+impl From<u32> for Words {
+    fn from(word: u32) -> Self {
+        Self::new(&[word])
+    }
+}
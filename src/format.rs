@@ -0,0 +1,69 @@
+//! LEDが受け取るデータの並びです。
+
+use smart_leds_trait::{RGB8, RGBW};
+
+use crate::grb_word;
+
+/// LEDが受け取るデータの並び
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelFormat {
+    /// WS2812などの3色(GRB)
+    #[default]
+    Grb,
+    /// 3色(RGB)
+    Rgb,
+    /// SK6812 RGBWなどの4色(GRBW)
+    ///
+    /// [`PixelFormat::encode`]では白のチャンネルを0として送ります。
+    Grbw,
+}
+
+impl PixelFormat {
+    /// 1ピクセルのビット数を返します。
+    pub const fn bits(self) -> u8 {
+        match self {
+            PixelFormat::Grb | PixelFormat::Rgb => 24,
+            PixelFormat::Grbw => 32,
+        }
+    }
+
+    /// `pixels`をこの並びのワードに変換して`words`に書き込み、書き込んだワード数を返します。
+    ///
+    /// `words`が短い場合は入る分だけ変換します。
+    pub fn encode_frame(self, pixels: &[RGB8], words: &mut [u32]) -> usize {
+        for (word, &pixel) in words.iter_mut().zip(pixels) {
+            *word = self.encode(pixel);
+        }
+        pixels.len().min(words.len())
+    }
+
+    /// 色をFIFOに書き込むワードに変換します。
+    ///
+    /// データはワードの上位ビットから詰めます。
+    pub fn encode(self, color: RGB8) -> u32 {
+        let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+        match self {
+            PixelFormat::Grb | PixelFormat::Grbw => grb_word(color),
+            PixelFormat::Rgb => r << 24 | g << 16 | b << 8,
+        }
+    }
+
+    /// 白のチャンネルを含む色をFIFOに書き込むワードに変換します。
+    ///
+    /// 白のチャンネルがない並びでは白を捨てます。
+    pub fn encode_rgbw(self, color: RGBW<u8>) -> u32 {
+        let word = self.encode(RGB8::new(color.r, color.g, color.b));
+        match self {
+            PixelFormat::Grbw => word | color.a.0 as u32,
+            PixelFormat::Grb | PixelFormat::Rgb => word,
+        }
+    }
+}
+
+/// `pixels`をGRBの並びのワードに変換して`words`に書き込み、書き込んだワード数を返します。
+///
+/// ハードウェアを使わないので、ホストでのテストやDMAのバッファの準備に使えます。
+/// [`PixelFormat::encode_frame`]も参照してください。
+pub fn encode_frame(pixels: &[RGB8], words: &mut [u32]) -> usize {
+    PixelFormat::Grb.encode_frame(pixels, words)
+}
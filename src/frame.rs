@@ -0,0 +1,156 @@
+//! メモリを節約するためのフレームの表現です。
+//!
+//! どれも送信中にイテレーターで[`RGB8`]に展開するので、
+//! 展開したフレームをメモリに置く必要はありません。
+
+use smart_leds_trait::RGB8;
+
+/// `len`個のピクセルの色を`shader(index, t)`で計算しながら返すイテレーター
+///
+/// 計算した色はバッファに置かずにそのまま送れます。
+/// `t`はフレームの時刻で、単位は呼び出し側で決めてください(ミリ秒など)。
+///
+/// ```ignore
+/// ws.write(shade(60, now_ms, |i, t| hue(i as u32 * 4 + t / 10)))?;
+/// ```
+pub fn shade<F>(len: usize, t: u32, mut shader: F) -> impl ExactSizeIterator<Item = RGB8>
+where
+    F: FnMut(usize, u32) -> RGB8,
+{
+    (0..len).map(move |index| shader(index, t))
+}
+
+/// パレットの番号を1ピクセル1バイトで持つフレーム
+///
+/// 24bitの色を直接持つ場合の約3分の1のメモリで済みます。
+/// パレットにない番号のピクセルは消灯します。
+#[derive(Debug, Clone, Copy)]
+pub struct Indexed<'a> {
+    palette: &'a [RGB8],
+    indices: &'a [u8],
+}
+
+impl<'a> Indexed<'a> {
+    /// `palette`は最大256色です。
+    pub fn new(palette: &'a [RGB8], indices: &'a [u8]) -> Self {
+        Self { palette, indices }
+    }
+
+    /// ピクセルの数を返します。
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// ピクセルがないかを返します。
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for Indexed<'a> {
+    type Item = RGB8;
+    type IntoIter = IndexedIter<'a>;
+
+    fn into_iter(self) -> IndexedIter<'a> {
+        IndexedIter {
+            palette: self.palette,
+            indices: self.indices.iter(),
+        }
+    }
+}
+
+/// [`Indexed`]を展開するイテレーター
+#[derive(Debug, Clone)]
+pub struct IndexedIter<'a> {
+    palette: &'a [RGB8],
+    indices: core::slice::Iter<'a, u8>,
+}
+
+impl Iterator for IndexedIter<'_> {
+    type Item = RGB8;
+
+    fn next(&mut self) -> Option<RGB8> {
+        let &index = self.indices.next()?;
+        Some(
+            self.palette
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default(),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for IndexedIter<'_> {}
+
+/// ランレングス圧縮したフレーム
+///
+/// `[個数, R, G, B]`の4バイトを並べたデータです。
+/// 同じ色が続くピクセルを1つにまとめるので、単色の部分が多いアニメーションを
+/// 小さく保存できます。256個以上続く場合は分けて書いてください。
+/// 最後の4バイトに満たない部分は無視します。
+///
+/// ```ignore
+/// // 赤が10個、消灯が50個
+/// static FRAME: [u8; 8] = [10, 255, 0, 0, 50, 0, 0, 0];
+/// ws.write(Rle::new(&FRAME))?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Rle<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Rle<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// 展開したピクセルの数を返します。
+    pub fn len(&self) -> usize {
+        self.data.chunks_exact(4).map(|run| run[0] as usize).sum()
+    }
+
+    /// ピクセルがないかを返します。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a> IntoIterator for Rle<'a> {
+    type Item = RGB8;
+    type IntoIter = RleIter<'a>;
+
+    fn into_iter(self) -> RleIter<'a> {
+        RleIter {
+            runs: self.data.chunks_exact(4),
+            color: RGB8::default(),
+            remaining: 0,
+        }
+    }
+}
+
+/// [`Rle`]を展開するイテレーター
+#[derive(Debug, Clone)]
+pub struct RleIter<'a> {
+    runs: core::slice::ChunksExact<'a, u8>,
+    color: RGB8,
+    /// 今の色の残りの個数
+    remaining: u8,
+}
+
+impl Iterator for RleIter<'_> {
+    type Item = RGB8;
+
+    fn next(&mut self) -> Option<RGB8> {
+        while self.remaining == 0 {
+            let run = self.runs.next()?;
+            self.remaining = run[0];
+            self.color = RGB8::new(run[1], run[2], run[3]);
+        }
+        self.remaining -= 1;
+        Some(self.color)
+    }
+}
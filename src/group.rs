@@ -0,0 +1,420 @@
+//! 複数のストリップを同じリセット時間でラッチさせるためのまとまりです。
+//!
+//! 別々のピンやステートマシンで駆動しているストリップを順に更新すると、
+//! ストリップごとに表示が切り替わる時刻がずれて、隣り合うストリップの境目がちらつきます。
+//! [`StripGroup`]は全てのストリップのFIFOへ1ワードずつ交互に書き込むので、
+//! 全てのストリップがほぼ同時に送り終わり、同じリセット時間の中でラッチされます。
+
+use core::iter::{Fuse, Peekable};
+
+use cortex_m::prelude::_embedded_hal_timer_CountDown;
+use rp235x_hal::{
+    fugit::ExtU32,
+    gpio::AnyPin,
+    pio::{PIOExt, StateMachineIndex},
+    timer::{CountDown, TimerDevice},
+};
+use smart_leds_trait::{RGB8, RGBW, SmartLedsWrite, White};
+
+use crate::{
+    Error, RESET_MICROS, Ws2812Direct,
+    feed::Feeder,
+    grb_word,
+    ops::{scale_channel, scale_pixel},
+    pio_regs,
+};
+
+/// [`StripGroup`]にまとめられるストリップ
+///
+/// 型の違うドライバをまとめるときは`&mut dyn Strip`を使ってください。
+pub trait Strip {
+    /// FIFOにワードを書き込みます。FIFOが一杯の場合は`false`を返します。
+    fn write_word(&mut self, word: u32) -> bool;
+
+    /// FIFOが空になり、ステートマシンがストールするまで待ちます。
+    fn wait_drained(&self);
+
+    /// FIFOが空くのを待つ間の1回分の処理をします。
+    fn wait(&self) {
+        cortex_m::asm::nop();
+    }
+
+    /// FIFOの空きのワード数を返します。
+    ///
+    /// `Some`を返すと、空きの数だけ[`Strip::write_word_unchecked`]でまとめて書き込みます。
+    /// 空きを調べられない場合は`None`を返し、1ワードずつ[`Strip::write_word`]で書き込みます。
+    fn vacancy(&mut self) -> Option<usize> {
+        None
+    }
+
+    /// FIFOが一杯かを確かめずにワードを書き込みます。
+    fn write_word_unchecked(&mut self, word: u32) {
+        let _ = self.write_word(word);
+    }
+
+    /// 色をこのストリップのFIFOに書き込むワードに変換します。
+    fn encode(&self, color: RGB8) -> u32 {
+        grb_word(color)
+    }
+
+    /// 白のチャンネルを含む色をこのストリップのFIFOに書き込むワードに変換します。
+    ///
+    /// 白のLEDがないストリップでは白のチャンネルを捨てます。
+    fn encode_rgbw(&self, color: RGBW<u8>) -> u32 {
+        self.encode(RGB8::new(color.r, color.g, color.b))
+    }
+
+    /// ラッチに必要なリセット時間(µs)を返します。
+    fn reset_micros(&self) -> u32 {
+        RESET_MICROS
+    }
+}
+
+impl<P, SM, I> Strip for Ws2812Direct<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    fn write_word(&mut self, word: u32) -> bool {
+        // フレームを書き込み始めたときにストールのフラグを消しているので、
+        // フラグが立っていればフレームの途中でFIFOが空になっています。
+        if self.parts.checking && self.tx.has_stalled() {
+            self.tx.clear_stalled_flag();
+            self.parts.underrun = true;
+            self.parts.stats.stalls = self.parts.stats.stalls.wrapping_add(1);
+        }
+        self.tx.write(word)
+    }
+
+    fn wait_drained(&self) {
+        Ws2812Direct::wait_drained(self)
+    }
+
+    fn wait(&self) {
+        Ws2812Direct::wait(self)
+    }
+
+    fn vacancy(&mut self) -> Option<usize> {
+        let regs = pio_regs::<P>();
+        let level = (regs.flevel().read().bits() >> (SM::id() * 8)) & 0xf;
+        let joined = regs.sm(SM::id()).sm_shiftctrl().read().fjoin_tx().bit();
+        let depth = if joined { 8 } else { 4 };
+        let room = depth - level as usize;
+        if self.parts.checking && self.tx.has_stalled() {
+            self.tx.clear_stalled_flag();
+            self.parts.underrun = true;
+            self.parts.stats.stalls = self.parts.stats.stalls.wrapping_add(1);
+        }
+        Some(room)
+    }
+
+    fn write_word_unchecked(&mut self, word: u32) {
+        // Safety: `vacancy`で空きを確かめてから、このステートマシンのFIFOにだけ書き込みます。
+        pio_regs::<P>()
+            .txf(SM::id())
+            .write(|w| unsafe { w.bits(word) });
+    }
+
+    fn encode(&self, color: RGB8) -> u32 {
+        self.parts
+            .format
+            .encode(scale_pixel(color, self.fraction()))
+    }
+
+    fn encode_rgbw(&self, color: RGBW<u8>) -> u32 {
+        let fraction = self.fraction();
+        let rgb = scale_pixel(RGB8::new(color.r, color.g, color.b), fraction);
+        let white = White(scale_channel(color.a.0, fraction));
+        self.parts
+            .format
+            .encode_rgbw(RGBW::new_alpha(rgb.r, rgb.g, rgb.b, white))
+    }
+
+    fn reset_micros(&self) -> u32 {
+        self.parts.reset_micros
+    }
+}
+
+impl<S: Strip + ?Sized> Strip for &mut S {
+    fn write_word(&mut self, word: u32) -> bool {
+        (**self).write_word(word)
+    }
+
+    fn wait_drained(&self) {
+        (**self).wait_drained()
+    }
+
+    fn wait(&self) {
+        (**self).wait()
+    }
+
+    fn vacancy(&mut self) -> Option<usize> {
+        (**self).vacancy()
+    }
+
+    fn write_word_unchecked(&mut self, word: u32) {
+        (**self).write_word_unchecked(word)
+    }
+
+    fn encode(&self, color: RGB8) -> u32 {
+        (**self).encode(color)
+    }
+
+    fn encode_rgbw(&self, color: RGBW<u8>) -> u32 {
+        (**self).encode_rgbw(color)
+    }
+
+    fn reset_micros(&self) -> u32 {
+        (**self).reset_micros()
+    }
+}
+
+/// 短いストリップを最も長いストリップに揃えるときに送るデータ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// 消灯
+    #[default]
+    Black,
+    /// 最後のピクセルと同じ色
+    RepeatLast,
+}
+
+/// フレームの後ろに`Padding`を無限に続けるイテレーター
+struct Padded<T: Iterator> {
+    iter: Peekable<Fuse<T>>,
+    padding: Padding,
+    last: RGB8,
+}
+
+impl<T> Padded<T>
+where
+    T: Iterator,
+{
+    /// 元のフレームを全て返し終えたかを返します。
+    fn is_exhausted(&mut self) -> bool {
+        self.iter.peek().is_none()
+    }
+}
+
+impl<T> Iterator for Padded<T>
+where
+    T: Iterator,
+    T::Item: Into<RGB8>,
+{
+    type Item = RGB8;
+
+    fn next(&mut self) -> Option<RGB8> {
+        if let Some(item) = self.iter.next() {
+            self.last = item.into();
+            return Some(self.last);
+        }
+        match self.padding {
+            Padding::Black => Some(RGB8::default()),
+            Padding::RepeatLast => Some(self.last),
+        }
+    }
+}
+
+/// 複数のストリップを同時に更新するまとまり
+pub struct StripGroup<'timer, D, S, const N: usize>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    cd: CountDown<'timer, D>,
+    strips: [S; N],
+    padding: Padding,
+}
+
+impl<'timer, D, S, const N: usize> StripGroup<'timer, D, S, N>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    pub fn new(strips: [S; N], cd: CountDown<'timer, D>) -> Self {
+        Self {
+            cd,
+            strips,
+            padding: Padding::Black,
+        }
+    }
+
+    /// 短いストリップに送るデータを設定します。
+    pub fn set_padding(&mut self, padding: Padding) {
+        self.padding = padding;
+    }
+
+    /// ストリップとカウントダウンを返します。
+    pub fn free(self) -> ([S; N], CountDown<'timer, D>) {
+        (self.strips, self.cd)
+    }
+
+    /// `frames`をそれぞれのストリップへ同時に書き込みます。
+    ///
+    /// 色はストリップごとのデータの並びに変換されるので、
+    /// GRBのWS2812とGRBWのSK6812のように種類の違うLEDを混ぜられます。
+    /// 長さの違うフレームを渡した場合、短いストリップには最も長いフレームが
+    /// 終わるまで[`Padding`]のデータを送るので、全てのストリップが揃って送り終わります。
+    /// 全てのストリップの前のフレームがラッチされるまで待ってから送り始めます。
+    pub fn write_all<T, C>(&mut self, frames: [T; N])
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<RGB8>,
+    {
+        self.latch();
+
+        let padding = self.padding;
+        let mut feeders = frames.map(|frame| {
+            Feeder::new(Padded {
+                iter: frame.into_iter().fuse().peekable(),
+                padding,
+                last: RGB8::default(),
+            })
+        });
+        // 最も長いフレームの最後のピクセルを書き込んだら、それ以上は埋めません。
+        while feeders
+            .iter_mut()
+            .any(|feeder| feeder.has_pending() || !feeder.iter_mut().is_exhausted())
+        {
+            for (feeder, strip) in feeders.iter_mut().zip(self.strips.iter_mut()) {
+                feeder.feed(strip, feeder.written() + 1);
+            }
+        }
+    }
+
+    /// 全てのストリップが前のフレームを送り終え、リセット時間が経つまで待ちます。
+    fn latch(&mut self) {
+        let mut reset_micros = 0;
+        for strip in &self.strips {
+            strip.wait_drained();
+            reset_micros = reset_micros.max(strip.reset_micros());
+        }
+        self.cd.start(reset_micros.micros());
+        let _ = nb::block!(self.cd.wait());
+    }
+}
+
+/// 2本のピンに分けて接続した1本の長いストリップ
+///
+/// 先頭から`split`個のLEDを1本目に、残りを2本目に接続してください。
+/// アプリケーションからは0から始まる1本のストリップとして扱え、
+/// 2本は[`StripGroup`]と同じように同時にラッチされます。
+pub struct SplitStrip<'timer, D, S>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    group: StripGroup<'timer, D, S, 2>,
+    split: usize,
+}
+
+impl<'timer, D, S> SplitStrip<'timer, D, S>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    pub fn new(first: S, second: S, split: usize, cd: CountDown<'timer, D>) -> Self {
+        Self {
+            group: StripGroup::new([first, second], cd),
+            split,
+        }
+    }
+
+    /// ストリップとカウントダウンを返します。
+    pub fn free(self) -> ([S; 2], CountDown<'timer, D>) {
+        self.group.free()
+    }
+
+    /// `index`番目のLEDが何本目のストリップの何番目かを返します。
+    pub fn locate(&self, index: usize) -> (usize, usize) {
+        if index < self.split {
+            (0, index)
+        } else {
+            (1, index - self.split)
+        }
+    }
+
+    /// フレームを書き込みます。
+    ///
+    /// 2本のストリップへ同時に送るために、イテレーターを複製して後半を読み飛ばします。
+    pub fn write<T, C>(&mut self, frame: T)
+    where
+        T: IntoIterator<Item = C>,
+        T::IntoIter: Clone,
+        C: Into<RGB8>,
+    {
+        let iter = frame.into_iter();
+        let halves = [(0, self.split), (self.split, usize::MAX)]
+            .map(|(start, end)| iter.clone().take(end).skip(start));
+        self.group.write_all(halves);
+    }
+}
+
+/// 複数のストリップを1本の長いストリップとして扱うまとめ役
+///
+/// `lengths[i]`個ずつ順に各ストリップへ割り振るので、1本のストリップ向けに書いた
+/// エフェクトのコードをそのまま使えます。
+/// [`SmartLedsWrite`]のイテレーターは複製できないため、ストリップへは1本ずつ順に送ります。
+/// 全てのストリップを同時にラッチさせたい場合は[`StripGroup`]や[`SplitStrip`]を使ってください。
+pub struct Gang<'timer, D, S, const N: usize>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    group: StripGroup<'timer, D, S, N>,
+    lengths: [usize; N],
+}
+
+impl<'timer, D, S, const N: usize> Gang<'timer, D, S, N>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    pub fn new(strips: [S; N], lengths: [usize; N], cd: CountDown<'timer, D>) -> Self {
+        Self {
+            group: StripGroup::new(strips, cd),
+            lengths,
+        }
+    }
+
+    /// ストリップとカウントダウンを返します。
+    pub fn free(self) -> ([S; N], CountDown<'timer, D>) {
+        self.group.free()
+    }
+
+    /// 全体のLEDの数を返します。
+    pub fn len(&self) -> usize {
+        self.lengths.iter().sum()
+    }
+
+    /// LEDがないかを返します。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'timer, D, S, const N: usize> SmartLedsWrite for Gang<'timer, D, S, N>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        self.group.latch();
+
+        let mut iter = iterator.into_iter();
+        for (strip, &len) in self.group.strips.iter_mut().zip(&self.lengths) {
+            let mut feeder = Feeder::new(iter.by_ref().take(len));
+            while !feeder.feed(strip, usize::MAX) {
+                strip.wait();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! HD108のドライバです。
+//!
+//! HD108はAPA102と同じクロック付きの2線式で、1チャンネル16bitの色と、
+//! ピクセルごとにチャンネルごとの5bitのゲインを持ちます。
+//! PIOのプログラムは[`Apa102`]と同じものを使います。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB, RGB16, SmartLedsWrite};
+
+use crate::{Apa102, Error};
+
+/// HD108に送る1ピクセル
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Hd108Pixel {
+    pub color: RGB16,
+    /// チャンネルごとのゲイン(0から31)
+    pub gain: RGB<u8>,
+}
+
+impl From<RGB16> for Hd108Pixel {
+    /// ゲインは全てのチャンネルで最大になります。
+    fn from(color: RGB16) -> Self {
+        Self {
+            color,
+            gain: RGB::new(31, 31, 31),
+        }
+    }
+}
+
+/// HD108のドライバ
+pub struct Hd108<P, SM, I, C>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    C: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    clocked: Apa102<P, SM, I, C>,
+}
+
+impl<P, SM, I, C> Hd108<P, SM, I, C>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    C: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// `bit_rate`はクロックの周波数です。
+    pub fn new(
+        data: I,
+        clock: C,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        bit_rate: HertzU32,
+    ) -> Self {
+        let clocked = Apa102::new(data, clock, pio, sm, clock_freq, bit_rate);
+        Self { clocked }
+    }
+}
+
+impl<P, SM, I, C> SmartLedsWrite for Hd108<P, SM, I, C>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    C: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = Hd108Pixel;
+    type Error = Error;
+    fn write<T, J>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<Self::Color>,
+    {
+        // スタートフレーム(128bitの0)
+        for _ in 0..4 {
+            self.clocked.write_word(0);
+        }
+
+        let mut count: usize = 0;
+        for item in iterator {
+            let Hd108Pixel { color, gain } = item.into();
+            let clamp = |value: u8| value.min(31) as u32;
+            let header = 0x8000 | clamp(gain.r) << 10 | clamp(gain.g) << 5 | clamp(gain.b);
+            let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+            self.clocked.write_word(header << 16 | r);
+            self.clocked.write_word(g << 16 | b);
+            count += 1;
+        }
+
+        self.clocked.end_frame(count);
+        Ok(())
+    }
+}
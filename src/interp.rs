@@ -0,0 +1,121 @@
+//! 一定のフレームレートで出力し、アプリケーションが渡したフレームの間を補間する仕組みです。
+//!
+//! 描画が遅かったり間隔がばらついたりしても、出力は一定の間隔で滑らかに変わります。
+//! 新しいフレームを渡すと、その時点で表示している色から新しいフレームへ、
+//! 前回フレームを渡してからの時間をかけて直線的に変えていくので、
+//! 表示は渡したフレームより1フレーム分の間隔だけ遅れます。
+//!
+//! ```ignore
+//! let mut interp = Interpolator::<_, 60>::new(100.Hz(), timer.count_down());
+//! loop {
+//!     if let Some(frame) = renderer.poll() {
+//!         interp.submit(frame);
+//!     }
+//!     interp.step(&mut ws)?;
+//! }
+//! ```
+
+use cortex_m::prelude::_embedded_hal_timer_CountDown;
+use rp235x_hal::{
+    fugit::{ExtU32, HertzU32},
+    timer::{CountDown, TimerDevice},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::compose::lerp;
+
+/// `N`個のLEDのフレームの間を補間して一定の間隔で出力するもの
+pub struct Interpolator<'timer, D, const N: usize>
+where
+    D: TimerDevice,
+{
+    /// 補間の始まりの色
+    from: [RGB8; N],
+    /// 補間の終わりの色(最後に渡されたフレーム)
+    to: [RGB8; N],
+    /// 補間にかける出力のフレーム数
+    span: u32,
+    /// 補間を始めてから出力したフレーム数
+    progress: u32,
+    /// 最後にフレームを渡されてから出力したフレーム数
+    since_submit: u32,
+    /// 1フレームの時間(µs)
+    period_micros: u32,
+    cd: CountDown<'timer, D>,
+    started: bool,
+}
+
+impl<'timer, D, const N: usize> Interpolator<'timer, D, N>
+where
+    D: TimerDevice,
+{
+    /// `output_rate`で出力します。最初は全て消灯です。
+    pub fn new(output_rate: HertzU32, cd: CountDown<'timer, D>) -> Self {
+        assert!(output_rate.to_Hz() > 0, "output_rate must not be 0.");
+        Self {
+            from: [RGB8::default(); N],
+            to: [RGB8::default(); N],
+            span: 1,
+            progress: 1,
+            since_submit: 0,
+            period_micros: 1_000_000 / output_rate.to_Hz(),
+            cd,
+            started: false,
+        }
+    }
+
+    /// カウントダウンを返します。
+    pub fn free(self) -> CountDown<'timer, D> {
+        self.cd
+    }
+
+    /// 新しいフレームを渡します。
+    ///
+    /// 今表示している色から、前回フレームを渡してからと同じ時間をかけて`frame`に変えます。
+    /// `N`個に満たない分は消灯にし、`N`個を超える分は無視します。
+    pub fn submit<T, C>(&mut self, frame: T)
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<RGB8>,
+    {
+        let t = self.t();
+        for (from, to) in self.from.iter_mut().zip(&self.to) {
+            *from = lerp(*from, *to, t);
+        }
+        let mut frame = frame.into_iter();
+        for to in &mut self.to {
+            *to = frame.next().map_or(RGB8::default(), Into::into);
+        }
+        self.span = self.since_submit.max(1);
+        self.progress = 0;
+        self.since_submit = 0;
+    }
+
+    /// 次の出力の時刻まで待って、補間したフレームを送ります。
+    pub fn step<W>(&mut self, driver: &mut W) -> Result<(), W::Error>
+    where
+        W: SmartLedsWrite,
+        RGB8: Into<W::Color>,
+    {
+        if self.started {
+            let _ = nb::block!(self.cd.wait());
+        }
+        self.cd.start(self.period_micros.micros());
+        self.started = true;
+
+        self.progress = (self.progress + 1).min(self.span);
+        self.since_submit = self.since_submit.saturating_add(1);
+        let t = self.t();
+        driver.write(
+            self.from
+                .iter()
+                .zip(&self.to)
+                .map(|(&from, &to)| lerp(from, to, t)),
+        )
+    }
+
+    /// 補間の進み具合(0から255)を返します。
+    fn t(&self) -> u8 {
+        (self.progress as u64 * 255 / self.span as u64) as u8
+    }
+}
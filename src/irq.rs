@@ -0,0 +1,194 @@
+//! PIOの"TX not full"割り込みでFIFOを補充するドライバです。
+//!
+//! `write`はフレームを内部のバッファにコピーしてすぐに戻り、
+//! 残りは割り込みハンドラの中で[`Ws2812Irq::on_interrupt`]が送ります。
+//! 割り込みハンドラと共有するため、`critical_section::Mutex<RefCell<_>>`などに入れて使ってください。
+//!
+//! ```ignore
+//! static WS: Mutex<RefCell<Option<Ws2812Irq<PIO0, SM0, Pin, 64>>>> = Mutex::new(RefCell::new(None));
+//!
+//! #[interrupt]
+//! fn PIO0_IRQ_0() {
+//!     critical_section::with(|cs| {
+//!         if let Some(ws) = WS.borrow_ref_mut(cs).as_mut() {
+//!             ws.on_interrupt();
+//!         }
+//!     });
+//! }
+//! ```
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, PixelFormat, Stats, Strip, Ws2812Direct};
+
+/// 割り込みでFIFOを補充するWS2812ドライバ
+///
+/// 最大`N`ピクセルまで送れます。
+/// [`Ws2812Irq::set_length`]で設定した長さまでの消灯のデータはバッファに含めずに送ります。
+/// フレーム間のリセット時間(50µs以上)は呼び出し側で確保してください。
+pub struct Ws2812Irq<P, SM, I, const N: usize>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    irq: PioIRQ,
+    buffer: [u32; N],
+    /// バッファに書き込んだピクセル数
+    filled: usize,
+    /// 消灯のデータを含めて送るピクセル数
+    len: usize,
+    cursor: usize,
+    /// 消灯のデータのワード
+    black: u32,
+}
+
+impl<P, SM, I, const N: usize> Ws2812Irq<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// `irq`はFIFOの補充に使う割り込み線です。
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        irq: PioIRQ,
+    ) -> Self {
+        let driver = Ws2812Direct::new(pin, pio, sm, clock_freq);
+        Self {
+            driver,
+            irq,
+            buffer: [0; N],
+            filled: 0,
+            len: 0,
+            cursor: 0,
+            black: 0,
+        }
+    }
+
+    /// `format`の並びで送るように設定します。
+    ///
+    /// [`PixelFormat::Grbw`]は使えません。
+    pub fn set_format(&mut self, format: PixelFormat) {
+        assert!(format.bits() == 24, "only 24-bit formats are supported.");
+        self.driver.parts.format = format;
+    }
+
+    /// 全体の明るさを設定します。[`Ws2812Direct::set_brightness`]を参照してください。
+    ///
+    /// 次に`write`したフレームから反映されます。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.driver.set_brightness(brightness);
+    }
+
+    /// ストリップのLEDの数を設定します。[`Ws2812Direct::set_length`]を参照してください。
+    ///
+    /// 長すぎるフレームは何も送らずに[`Error::LengthMismatch`]になります。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.driver.set_length(length);
+    }
+
+    /// 送ったフレームなどの数を返します。[`Ws2812Direct::stats`]を参照してください。
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    /// フレームをFIFOへ送っている途中かを返します。
+    pub fn is_busy(&self) -> bool {
+        self.cursor < self.len
+    }
+
+    /// 送っている途中のフレームを捨てます。
+    ///
+    /// 次のフレームはリセット時間が経ってから書き込んでください。
+    pub fn abort(&mut self) {
+        self.driver.tx.disable_tx_not_full_interrupt(self.irq);
+        self.len = 0;
+        self.cursor = 0;
+        self.driver.parts.checking = false;
+        self.driver.abort();
+    }
+
+    /// 割り込みハンドラから呼んでください。
+    ///
+    /// FIFOに入るだけ書き込み、フレームを全て書き込んだら割り込みを無効にして統計を更新します。
+    pub fn on_interrupt(&mut self) {
+        while self.cursor < self.len {
+            let word = if self.cursor < self.filled {
+                self.buffer[self.cursor]
+            } else {
+                self.black
+            };
+            if !Strip::write_word(&mut self.driver, word) {
+                break;
+            }
+            if self.cursor == 0 {
+                // 最初のワードは止まっているステートマシンに書き込むので、2ワード目から調べます。
+                self.driver.tx.clear_stalled_flag();
+                self.driver.parts.checking = true;
+            }
+            self.cursor += 1;
+        }
+        if !self.is_busy() {
+            self.driver.tx.disable_tx_not_full_interrupt(self.irq);
+            if self.driver.parts.checking {
+                let _ = self.driver.finish_frame(self.len, Ok(()));
+            }
+        }
+    }
+}
+
+impl<P, SM, I, const N: usize> SmartLedsWrite for Ws2812Irq<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB8;
+    type Error = Error;
+    /// 前のフレームを送っている途中の場合は[`Error::Busy`]を、
+    /// ピクセルが`N`個やストリップの長さより多い場合は[`Error::LengthMismatch`]を返します。
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let mut iter = iterator.into_iter();
+        if self.is_busy() {
+            return Err(Error::Busy);
+        }
+        let limit = self.driver.frame_limit(&iter)?;
+        if iter.size_hint().0 > N {
+            return Err(Error::LengthMismatch);
+        }
+        let mut filled = 0;
+        for item in iter.by_ref().take(limit) {
+            *self.buffer.get_mut(filled).ok_or(Error::LengthMismatch)? =
+                Strip::encode(&self.driver, item.into());
+            filled += 1;
+        }
+        if iter.next().is_some() {
+            return Err(Error::LengthMismatch);
+        }
+        self.filled = filled;
+        self.len = self.driver.parts.length.unwrap_or(filled);
+        self.cursor = 0;
+        self.black = Strip::encode(&self.driver, RGB8::default());
+        self.driver.parts.underrun = false;
+        self.driver.parts.checking = false;
+        if self.len == 0 {
+            return self.driver.finish_frame(0, Ok(()));
+        }
+        self.driver.tx.enable_tx_not_full_interrupt(self.irq);
+        Ok(())
+    }
+}
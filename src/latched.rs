@@ -0,0 +1,210 @@
+//! リセット時間をPIOのプログラムの中で作るドライバです。
+//!
+//! 各ピクセルのワードの先頭に1ビットの印を付け、印が0のワードでフレームの終わりを表します。
+//! 終わりのワードには待つ回数が入っていて、ステートマシンがその回数だけループしてから
+//! 次のフレームを送り始めるので、タイマーを使わずに続けて書き込めます。
+//!
+//! 1ワードで25ビット取り出すので、24ビットの[`PixelFormat::Grb`]と[`PixelFormat::Rgb`]だけに対応します。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{InstalledProgram, PIO, PIOExt, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, PixelFormat, RESET_MICROS, Strip, Timing, Ws2812Direct};
+
+/// リセット時間の待機ループの1回のサイクル数
+const LATCH_LOOP_CYCLES: u32 = 16;
+
+/// リセット時間をプログラムで作るWS2812のプログラムをインストールします。
+fn install_latched<P: PIOExt>(pio: &mut PIO<P>, timing: Timing) -> InstalledProgram<P> {
+    let (t1, t2, t3) = (timing.t1(), timing.t2(), timing.t3());
+
+    // PIOに入れるプログラム
+    //
+    // top:
+    //     out x, 1             side 0          ; 0ならフレームの終わり
+    //     jmp !x latch         side 0
+    //     set y, 23            side 0
+    // bitloop:
+    //     out x, 1             side 0 [T3 - 1]
+    //     jmp !x do_zero       side 1 [T1 - 1]
+    //     jmp y-- bitloop      side 1 [T2 - 1]
+    //     jmp top              side 0 [T2 - 1]
+    // do_zero:
+    //     jmp y-- bitloop      side 0 [T2 - 1]
+    //     jmp top              side 0
+    // latch:
+    //     out x, 24            side 0
+    // latch_loop:
+    //     jmp x-- latch_loop   side 0 [15]
+    let mut a = pio::Assembler::<32>::new_with_side_set(pio::SideSet::new(false, 1, false));
+    let mut top = a.label();
+    let mut wrap_source = a.label();
+    let mut bitloop = a.label();
+    let mut do_zero = a.label();
+    let mut latch = a.label();
+    let mut latch_loop = a.label();
+    a.bind(&mut top);
+    a.out_with_side_set(pio::OutDestination::X, 1, 0);
+    a.jmp_with_side_set(pio::JmpCondition::XIsZero, &mut latch, 0);
+    a.set_with_side_set(pio::SetDestination::Y, 23, 0);
+    a.bind(&mut bitloop);
+    a.out_with_delay_and_side_set(pio::OutDestination::X, 1, t3 - 1, 0);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::XIsZero, &mut do_zero, t1 - 1, 1);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::YDecNonZero, &mut bitloop, t2 - 1, 1);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::Always, &mut top, t2 - 1, 0);
+    a.bind(&mut do_zero);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::YDecNonZero, &mut bitloop, t2 - 1, 0);
+    a.jmp_with_side_set(pio::JmpCondition::Always, &mut top, 0);
+    a.bind(&mut latch);
+    a.out_with_side_set(pio::OutDestination::X, 24, 0);
+    a.bind(&mut latch_loop);
+    a.jmp_with_delay_and_side_set(
+        pio::JmpCondition::XDecNonZero,
+        &mut latch_loop,
+        LATCH_LOOP_CYCLES as u8 - 1,
+        0,
+    );
+    a.bind(&mut wrap_source);
+    let program = a.assemble_with_wrap(wrap_source, top);
+
+    pio.install(&program).unwrap()
+}
+
+/// リセット時間をPIOのプログラムで作るWS2812ドライバ
+///
+/// [`Ws2812`](crate::Ws2812)と違ってカウントダウンを借りないので、寿命の引数もありません。
+/// `write`を続けて呼んでも、フレームの間には必ずリセット時間が入ります。
+pub struct Ws2812Latched<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    timing: Timing,
+    /// 終わりのワード
+    end_word: u32,
+}
+
+impl<P, SM, I> Ws2812Latched<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        Self::with_timing(pin, pio, sm, clock_freq, Timing::WS2812)
+    }
+
+    /// `timing`の波形で送るように初期化します。
+    pub fn with_timing(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        timing: Timing,
+    ) -> Self {
+        let installed = install_latched(pio, timing);
+        let driver = Ws2812Direct::with_program(pin, installed, sm, clock_freq, timing, 25);
+        let mut latched = Self {
+            driver,
+            timing,
+            end_word: 0,
+        };
+        latched.set_reset_micros(RESET_MICROS);
+        latched
+    }
+
+    /// `format`の並びで送るように設定します。
+    ///
+    /// [`PixelFormat::Grbw`]は使えません。
+    pub fn set_format(&mut self, format: PixelFormat) {
+        assert!(format.bits() == 24, "only 24-bit formats are supported.");
+        self.driver.parts.format = format;
+    }
+
+    /// フレームの間に入れるリセット時間(µs)を設定します。
+    pub fn set_reset_micros(&mut self, reset_micros: u32) {
+        self.driver.set_reset_micros(reset_micros);
+        let timing = self.timing;
+        let cycles =
+            reset_micros as u64 * timing.bit_rate().to_Hz() as u64 * timing.cycles_per_bit() as u64
+                / 1_000_000;
+        let loops = cycles.div_ceil(LATCH_LOOP_CYCLES as u64).clamp(1, 1 << 24) as u32;
+        self.end_word = (loops - 1) << 7;
+    }
+
+    /// ステートマシンを止め、プログラムをアンインストールして、ピンとステートマシンを返します。
+    pub fn free(self, pio: &mut PIO<P>) -> (I, UninitStateMachine<(P, SM)>) {
+        self.driver.free(pio)
+    }
+
+    /// 前のフレームを送り終え、リセット時間が経っているかを返します。
+    pub fn is_idle(&self) -> bool {
+        self.driver.is_idle()
+    }
+
+    /// 前のフレームを送っているか、リセット時間を待っているかを返します。
+    pub fn is_busy(&self) -> bool {
+        !self.is_idle()
+    }
+
+    /// 書き込んだフレームを送り終え、リセット時間が経つまで待ちます。
+    ///
+    /// リセット時間もステートマシンが作るので、ステートマシンが止まるまで待つだけです。
+    pub fn flush(&self) {
+        self.driver.wait_idle();
+    }
+
+    /// 送っている途中のフレームを捨てます。
+    ///
+    /// 続けてリセット時間を送るので、次のフレームはすぐに書き込めます。
+    pub fn abort(&mut self) {
+        self.driver.abort();
+        self.write_word(self.end_word);
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。[`Ws2812Direct::set_wait_hook`]を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.driver.set_wait_hook(hook);
+    }
+
+    fn write_word(&mut self, word: u32) {
+        while !self.driver.tx.write(word) {
+            self.driver.wait();
+        }
+        // FIFOにワードがある間はストールしないので、前のフレームのストールのフラグを消しておきます。
+        self.driver.tx.clear_stalled_flag();
+    }
+}
+
+impl<P, SM, I> SmartLedsWrite for Ws2812Latched<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        for item in iterator {
+            let word = Strip::encode(&self.driver, item.into());
+            self.write_word((1 << 31) | (word >> 1));
+        }
+        self.write_word(self.end_word);
+        Ok(())
+    }
+}
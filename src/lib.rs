@@ -4,16 +4,349 @@
 //!
 //! このクレートはws2812-pioを大いに参考にしています。
 
+pub mod apa102;
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod buffered;
+pub mod chip;
+pub mod color;
+pub mod compose;
+pub mod config;
+pub mod correction;
+pub mod dither;
+pub mod dma;
+pub mod error;
+pub mod fade;
+mod feed;
+pub mod format;
+pub mod frame;
+pub mod group;
+pub mod hd108;
+pub mod interp;
+pub mod irq;
+pub mod latched;
+pub mod mirror;
+pub mod mux;
+pub mod ops;
+pub mod packed;
+#[cfg(feature = "palette")]
+pub mod palette_interop;
+pub mod parallel;
+pub mod pending;
+pub mod player;
+pub mod pool;
+pub mod program;
+pub mod queue;
+pub mod rgbcct;
+pub mod rgbw;
+#[cfg(feature = "rtic")]
+pub mod rtic;
+pub mod stats;
+pub mod sync;
+pub mod timing;
+pub mod tm1814;
+pub mod trigger;
+pub mod wall;
+pub mod watchdog;
+pub mod wide;
+
+pub use apa102::Apa102;
+#[cfg(feature = "async")]
+pub use asynch::Ws2812Async;
+#[cfg(feature = "embassy")]
+pub use asynch::Ws2812Embassy;
+pub use buffered::{DoubleBuffered, Ws2812Buffered};
+pub use chip::Chip;
+pub use color::{Downscaled, Hsv};
+pub use compose::{BlendMode, Composite, Layer, Mask};
+pub use config::Config;
+pub use correction::{
+    ChannelGamma, ColorMatrix, Corrected, Correction, DimmingCurve, Gamma, WhiteBalance,
+};
+pub use dither::Dithered;
+pub use dma::{Ws2812Dma, Ws2812DmaBytes, Ws2812DmaContinuous, Ws2812DmaDouble};
+pub use error::Error;
+pub use fade::Fade;
+pub use format::{PixelFormat, encode_frame};
+pub use frame::{Indexed, Rle, shade};
+pub use group::{Gang, Padding, SplitStrip, Strip, StripGroup};
+pub use hd108::Hd108;
+pub use interp::Interpolator;
+pub use irq::Ws2812Irq;
+pub use latched::Ws2812Latched;
+pub use mirror::Ws2812Mirror;
+pub use mux::Ws2812Mux;
+pub use packed::Ws2812Packed;
+#[cfg(feature = "palette")]
+pub use palette_interop::{PaletteColor, PaletteWrite};
+pub use parallel::Ws2812Parallel;
+pub use pending::PendingWrite;
+pub use player::Player;
+pub use pool::{PooledStrip, Ws2812Pool};
+pub use program::{ProgramVariant, SharedProgram};
+pub use queue::Ws2812Queue;
+pub use rgbcct::Ws2805;
+pub use rgbw::{Sk6812Rgbw, WhiteMode};
+#[cfg(feature = "rtic")]
+pub use rtic::Ws2812Mono;
+pub use stats::{IntervalStats, Stats};
+pub use timing::Timing;
+pub use tm1814::Tm1814;
+pub use trigger::{Edge, Ws2812Triggered};
+pub use wall::VideoWall;
+pub use watchdog::Watchdog;
+pub use wide::Ws2816;
+
 use cortex_m::prelude::_embedded_hal_timer_CountDown;
 use rp235x_hal::{
-    fugit::{ExtU32, HertzU32},
+    fugit::{ExtU32, HertzU32, MicrosDurationU32},
     gpio::AnyPin,
+    pac,
     pio::{Buffers, PIOBuilder, PinDir, ShiftDirection},
-    pio::{PIO, PIOExt, StateMachineIndex, Tx, UninitStateMachine},
-    timer::{CountDown, TimerDevice},
+    pio::{InstalledProgram, PIO, PIOExt, Rx, StateMachine, StateMachineIndex},
+    pio::{PioIRQ, Running, Stopped, Tx, UninitStateMachine},
+    timer::{CountDown, Instant, Timer, TimerDevice},
 };
 use smart_leds_trait::{RGB8, SmartLedsWrite};
 
+use dma::DRAIN_WORDS;
+use feed::{Encoding, Feeder, Frame};
+
+/// リセット時間[µs]
+const RESET_MICROS: u32 = 70;
+
+/// 色をFIFOに書き込むワード(`0xGGRRBB00`)に変換します。
+fn grb_word(color: RGB8) -> u32 {
+    let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+    g << 24 | r << 16 | b << 8
+}
+
+fn pio_regs<P: PIOExt>() -> &'static pac::pio0::RegisterBlock {
+    // Safety: 読み出しと、アトミックなエイリアスやビットごとの操作を通した書き込みしかしません。
+    unsafe {
+        match P::id() {
+            0 => &*pac::PIO0::ptr(),
+            _ => &*pac::PIO1::ptr(),
+        }
+    }
+}
+
+/// 1ビットを`cycles_per_bit`サイクルで`bit_rate`にするクロック分周比を計算します。
+fn clock_divisor(clock_freq: HertzU32, bit_rate: HertzU32, cycles_per_bit: u32) -> (u16, u8) {
+    let bit_freq = bit_rate * cycles_per_bit;
+    let mut int = clock_freq / bit_freq;
+    let rem = clock_freq - (int * bit_freq);
+    let frac = (rem * 256) / bit_freq;
+    assert!(
+        (1..=65536).contains(&int) && (int != 65536 || frac == 0),
+        "(System Clock / {}) must be within [1.0, 65536.0].",
+        bit_freq.to_kHz()
+    );
+    if int == 65536 {
+        int = 0;
+    }
+    (int as u16, frac as u8)
+}
+
+/// `start`から経った時間を返します。
+fn elapsed_micros<D: TimerDevice>(timer: &Timer<D>, start: Instant) -> MicrosDurationU32 {
+    let elapsed = timer.get_counter().checked_duration_since(start);
+    let micros = elapsed.map_or(0, |elapsed| elapsed.to_micros());
+    (micros.min(u32::MAX as u64) as u32).micros()
+}
+
+/// `timing`の波形を出力するプログラムをインストールします。
+fn install<P: PIOExt>(pio: &mut PIO<P>, timing: Timing) -> InstalledProgram<P> {
+    let (t1, t2, t3) = (timing.t1(), timing.t2(), timing.t3());
+
+    // PIOに入れるプログラム
+    //
+    // bitloop:
+    //     out x, 1           side 0 [T3 - 1]
+    //     jmp !x do_zero     side 1 [T1 - 1]
+    //     jmp bitloop        side 1 [T2 - 1]
+    // do_zero:
+    //     nop                side 0 [T2 - 1]
+    let mut a = pio::Assembler::<32>::new_with_side_set(pio::SideSet::new(false, 1, false));
+    let mut wrap_target = a.label();
+    let mut wrap_source = a.label();
+    let mut do_zero = a.label();
+    a.bind(&mut wrap_target);
+    a.out_with_delay_and_side_set(pio::OutDestination::X, 1, t3 - 1, 0);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::XIsZero, &mut do_zero, t1 - 1, 1);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::Always, &mut wrap_target, t2 - 1, 1);
+    a.bind(&mut do_zero);
+    a.nop_with_delay_and_side_set(t2 - 1, 0);
+    a.bind(&mut wrap_source);
+    let program = a.assemble_with_wrap(wrap_source, wrap_target);
+
+    pio.install(&program).unwrap()
+}
+
+/// [`ProgramVariant::Minimal`]のプログラムをインストールします。
+fn install_minimal<P: PIOExt>(pio: &mut PIO<P>, timing: Timing) -> InstalledProgram<P> {
+    let (t1, t2, t3) = (timing.t1(), timing.t2(), timing.t3());
+    assert!(
+        t1 <= 8 && t2 <= 8 && t3 <= 8,
+        "the minimal program supports up to 8 cycles."
+    );
+
+    // PIOに入れるプログラム
+    //
+    // .side_set 1 opt
+    // .wrap_target
+    //     out x, 1    side 0 [T3 - 1]
+    //     nop         side 1 [T1 - 1]
+    //     mov pins, x        [T2 - 1]
+    // .wrap
+    let mut a = pio::Assembler::<32>::new_with_side_set(pio::SideSet::new(true, 1, false));
+    let mut wrap_target = a.label();
+    let mut wrap_source = a.label();
+    a.bind(&mut wrap_target);
+    a.out_with_delay_and_side_set(pio::OutDestination::X, 1, t3 - 1, 0);
+    a.nop_with_delay_and_side_set(t1 - 1, 1);
+    a.mov_with_delay(
+        pio::MovDestination::PINS,
+        pio::MovOperation::None,
+        pio::MovSource::X,
+        t2 - 1,
+    );
+    a.bind(&mut wrap_source);
+    let program = a.assemble_with_wrap(wrap_source, wrap_target);
+
+    pio.install(&program).unwrap()
+}
+
+/// `side_set_base`のピンに出力するステートマシンを作ります。
+///
+/// ピンの向きの設定と起動は呼び出し側で行ってください。
+#[allow(clippy::type_complexity)]
+fn build_state_machine<P, SM>(
+    installed: InstalledProgram<P>,
+    sm: UninitStateMachine<(P, SM)>,
+    clock_freq: HertzU32,
+    timing: Timing,
+    side_set_base: u8,
+    pull_threshold: u8,
+) -> (StateMachine<(P, SM), Stopped>, Rx<(P, SM)>, Tx<(P, SM)>)
+where
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    let (int, frac) = clock_divisor(clock_freq, timing.bit_rate(), timing.cycles_per_bit());
+
+    PIOBuilder::from_installed_program(installed)
+        .buffers(Buffers::OnlyTx)
+        .side_set_pin_base(side_set_base)
+        .out_pins(side_set_base, 1)
+        .out_shift_direction(ShiftDirection::Left)
+        .autopull(true)
+        .pull_threshold(pull_threshold)
+        .clock_divisor_fixed_point(int, frac)
+        .build(sm)
+}
+
+/// FIFOが空くのを待つ方法
+///
+/// ピンやステートマシンの使い方が違うドライバでも、同じ方法で待てるようにまとめています。
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Waiter {
+    /// FIFOが空くのを待つ間に呼ぶ関数
+    hook: Option<fn()>,
+    /// FIFOが空くのを`wfe`で待つときに使う割り込み
+    irq: Option<PioIRQ>,
+}
+
+impl Waiter {
+    /// [`Ws2812Direct::set_wait_hook`]を参照してください。
+    pub(crate) fn set_hook(&mut self, hook: Option<fn()>) {
+        self.hook = hook;
+    }
+
+    /// [`Ws2812Direct::set_wait_for_event`]を参照してください。
+    pub(crate) fn set_irq(&mut self, irq: Option<PioIRQ>) {
+        if irq.is_some() {
+            // Safety: SEVONPENDのビットを立てるだけです。
+            unsafe {
+                (*cortex_m::peripheral::SCB::PTR)
+                    .scr
+                    .modify(|scr| scr | (1 << 4))
+            };
+        }
+        self.irq = irq;
+    }
+
+    /// `tx`のFIFOが空くのを待つ間の1回分の処理をします。
+    pub(crate) fn wait<P, SM>(&self, tx: &Tx<(P, SM)>)
+    where
+        P: PIOExt,
+        SM: StateMachineIndex,
+    {
+        match (self.hook, self.irq) {
+            (Some(hook), _) => hook(),
+            (None, Some(irq)) => Self::wait_for_event(tx, irq),
+            (None, None) => cortex_m::asm::nop(),
+        }
+    }
+
+    /// FIFOに空きができるまで`wfe`で眠ります。
+    fn wait_for_event<P, SM>(tx: &Tx<(P, SM)>, irq: PioIRQ)
+    where
+        P: PIOExt,
+        SM: StateMachineIndex,
+    {
+        // PIOn_IRQ_mの割り込み番号は15 + 2n + mです。
+        let number = 15 + 2 * P::id() + irq as usize;
+        tx.enable_tx_not_full_interrupt(irq);
+        // 割り込みが保留中のままだと次の保留でイベントが起きないので、先に消しておきます。
+        // Safety: ICPRは1を書いたビットの保留だけを消します。
+        unsafe { (*cortex_m::peripheral::NVIC::PTR).icpr[0].write(1 << number) };
+        if tx.is_full() {
+            cortex_m::asm::wfe();
+        }
+        tx.disable_tx_not_full_interrupt(irq);
+    }
+}
+
+/// ドライバのFIFO以外の部品
+///
+/// DMAで送信している間もFIFOとは別に持ち運べるようにまとめています。
+struct Parts<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    sm: StateMachine<(P, SM), Running>,
+    rx: Rx<(P, SM)>,
+    pin: I,
+    format: PixelFormat,
+    /// 1ビットの波形のタイミング
+    timing: Timing,
+    /// ラッチに必要なリセット時間(µs)
+    reset_micros: u32,
+    /// 1ワードを送るのにかかる時間(µs、切り上げ)
+    word_micros: u32,
+    /// ストリップのLEDの数
+    length: Option<usize>,
+    /// 全体の明るさ
+    brightness: u8,
+    /// 明るさの設定値を実際の明るさに変換する曲線
+    dimming: DimmingCurve,
+    /// プログラムを[`SharedProgram`]から借りている
+    shared: bool,
+    /// 書き込むたびにFIFOが空になっていないかを調べる
+    checking: bool,
+    /// 最後のフレームの途中でFIFOが空になった
+    underrun: bool,
+    /// FIFOが空くのを待つ方法
+    waiter: Waiter,
+    /// 送ったフレームなどの数
+    stats: Stats,
+}
+
 pub struct Ws2812Direct<P, SM, I>
 where
     I: AnyPin<Function = P::PinFunction>,
@@ -21,7 +354,7 @@ where
     SM: StateMachineIndex,
 {
     tx: Tx<(P, SM)>,
-    _pin: I,
+    parts: Parts<P, SM, I>,
 }
 
 impl<P, SM, I> Ws2812Direct<P, SM, I>
@@ -36,88 +369,561 @@ where
         sm: UninitStateMachine<(P, SM)>,
         clock_freq: HertzU32,
     ) -> Self {
-        const T1: u8 = 2; // start bit
-        const T2: u8 = 5; // data bit
-        const T3: u8 = 3; // stop bit
-        const CYCLES_PER_BIT: u32 = (T1 + T2 + T3) as u32;
-        const FREQ: HertzU32 = HertzU32::kHz(800);
-
-        // PIOに入れるプログラム
-        let program = pio_proc::pio_asm!(
-            ".side_set 1",
-            ".define public T1 2",
-            ".define public T2 5",
-            ".define public T3 3",
-            ".wrap_target",
-            "bitloop:",
-            "    out x, 1           side 0 [T3 - 1]",
-            "    jmp !x do_zero     side 1 [T1 - 1]",
-            "    jmp bitloop        side 1 [T2 - 1]",
-            "do_zero:",
-            "    nop                side 0 [T2 - 1]",
-            ".wrap",
-        );
+        Self::with_format(pin, pio, sm, clock_freq, PixelFormat::Grb)
+    }
+
+    /// `format`のデータを受け取るLEDを駆動するように初期化します。
+    pub fn with_format(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        format: PixelFormat,
+    ) -> Self {
+        let mut driver =
+            Self::with_pull_threshold(pin, pio, sm, clock_freq, Timing::WS2812, format.bits());
+        driver.parts.format = format;
+        driver
+    }
 
-        let installed = pio.install(&program.program).unwrap();
+    /// `timing`の波形で送るように初期化します。
+    ///
+    /// WS2811の400kHzモードなど、WS2812と違う速度のLEDに使います。
+    pub fn with_timing(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        timing: Timing,
+    ) -> Self {
+        Self::with_pull_threshold(pin, pio, sm, clock_freq, timing, 24)
+    }
+
+    /// T1、T2、T3のサイクル数をコンパイル時に指定して初期化します。
+    ///
+    /// サイクル数が範囲外の場合はコンパイルエラーになります。
+    ///
+    /// ```ignore
+    /// let ws = Ws2812Direct::with_cycles::<3, 4, 3>(pin, &mut pio, sm0, clock_freq, 800.kHz());
+    /// ```
+    pub fn with_cycles<const T1: u8, const T2: u8, const T3: u8>(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        bit_rate: HertzU32,
+    ) -> Self {
+        const {
+            assert!(
+                T1 >= 1 && T1 <= 16 && T2 >= 1 && T2 <= 16 && T3 >= 1 && T3 <= 16,
+                "T1, T2 and T3 must be within [1, 16]."
+            )
+        };
+        let timing = Timing::new(T1, T2, T3, bit_rate);
+        Self::with_pull_threshold(pin, pio, sm, clock_freq, timing, 24)
+    }
+
+    /// `chip`のタイミングとデータの並びで送るように初期化します。
+    pub fn with_chip(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        chip: Chip,
+    ) -> Self {
+        Self::with_config(pin, pio, sm, clock_freq, chip.into())
+    }
 
-        // 周波数の計算
-        let bit_freq = FREQ * CYCLES_PER_BIT;
-        let mut int = clock_freq / bit_freq;
-        let rem = clock_freq - (int * bit_freq);
-        let frac = (rem * 256) / bit_freq;
+    /// `config`のタイミング、リセット時間、データの並びで送るように初期化します。
+    pub fn with_config(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        config: Config,
+    ) -> Self {
+        let format = config.format;
+        let installed = match config.program {
+            ProgramVariant::Standard => install(pio, config.timing),
+            ProgramVariant::Minimal => install_minimal(pio, config.timing),
+        };
+        let mut driver =
+            Self::with_program(pin, installed, sm, clock_freq, config.timing, format.bits());
+        driver.parts.format = format;
+        driver.parts.reset_micros = config.reset_micros;
+        driver
+    }
+
+    /// `program`のプログラムを他のドライバと共有して初期化します。
+    ///
+    /// プログラムはPIOブロックごとに1回だけインストールされます。
+    /// このドライバは[`free_shared`](Self::free_shared)で解放してください。
+    pub fn new_shared(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        program: &mut SharedProgram<P>,
+    ) -> Self {
+        let installed = program.acquire(pio);
+        let mut driver = Self::with_program(pin, installed, sm, clock_freq, Timing::WS2812, 24);
+        driver.parts.shared = true;
+        driver
+    }
+
+    /// LEDが受け取るデータの並びを返します。
+    pub fn format(&self) -> PixelFormat {
+        self.parts.format
+    }
+
+    /// ラッチに必要なリセット時間(µs)を返します。
+    pub fn reset_micros(&self) -> u32 {
+        self.parts.reset_micros
+    }
+
+    /// ラッチに必要なリセット時間(µs)を設定します。
+    ///
+    /// WS2812B-V5や多くの互換品は280µs以上が必要です。
+    pub fn set_reset_micros(&mut self, reset_micros: u32) {
+        self.parts.reset_micros = reset_micros;
+    }
+
+    /// ストリップのLEDの数を設定します。
+    ///
+    /// 設定すると、短いフレームの後ろを消灯のデータで埋めるので、
+    /// 前のフレームの色がストリップの末尾に残りません。
+    /// 長すぎるフレームは[`Error::LengthMismatch`]になります。
+    /// スライスのイテレーターのように長さが分かる場合は、送る前に長すぎることを調べます。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.parts.length = length;
+    }
+
+    /// 今の設定のまま`fps`で更新し続けられるLEDの最大の数を返します。
+    pub fn max_leds(&self, fps: u32) -> usize {
+        self.config().max_leds(fps)
+    }
+
+    /// 設定したストリップの長さで`fps`のフレームレートを出せるかを調べます。
+    ///
+    /// 1フレームの送信時間とリセット時間の合計が`1 / fps`秒を超える場合は
+    /// [`Error::FrameRateTooHigh`]を返します。長さを設定していない場合は常に`Ok`です。
+    pub fn check_frame_rate(&self, fps: u32) -> Result<(), Error> {
+        match self.parts.length {
+            Some(length) if length > self.max_leds(fps) => Err(Error::FrameRateTooHigh),
+            _ => Ok(()),
+        }
+    }
+
+    /// 今の設定を返します。
+    fn config(&self) -> Config {
+        Config::new()
+            .timing(self.parts.timing)
+            .reset_micros(self.parts.reset_micros)
+            .format(self.parts.format)
+    }
+
+    /// ストリップのLEDの数を返します。
+    pub fn length(&self) -> Option<usize> {
+        self.parts.length
+    }
+
+    /// 全体の明るさを設定します。255で等倍です。
+    ///
+    /// 送るときに色を変換するので、渡したフレームの内容は変わりません。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.parts.brightness = brightness;
+    }
+
+    /// 全体の明るさを返します。
+    pub fn brightness(&self) -> u8 {
+        self.parts.brightness
+    }
+
+    /// 全体の明るさの設定値を実際の明るさに変換する曲線を設定します。
+    pub fn set_dimming_curve(&mut self, curve: DimmingCurve) {
+        self.parts.dimming = curve;
+    }
+
+    /// ステートマシンを止め、プログラムをアンインストールして、ピンとステートマシンを返します。
+    pub fn free(self, pio: &mut PIO<P>) -> (I, UninitStateMachine<(P, SM)>) {
         assert!(
-            (1..=65536).contains(&int) && (int != 65536 || frac == 0),
-            "(System Clock / {}) must be within [1.0, 65536.0].",
-            bit_freq.to_kHz()
+            !self.parts.shared,
+            "drivers created with new_shared must be freed with free_shared"
         );
-        if int == 65536 {
-            int = 0;
-        }
-        let int = int as u16;
-        let frac = frac as u8;
+        let (pin, sm, installed) = self.uninit();
+        pio.uninstall(installed);
+        (pin, sm)
+    }
+
+    /// 共有しているプログラムを返してステートマシンを止め、ピンとステートマシンを返します。
+    ///
+    /// プログラムを使うドライバがなくなるとアンインストールされます。
+    pub fn free_shared(
+        self,
+        pio: &mut PIO<P>,
+        program: &mut SharedProgram<P>,
+    ) -> (I, UninitStateMachine<(P, SM)>) {
+        assert!(
+            self.parts.shared,
+            "only drivers created with new_shared can be freed with free_shared"
+        );
+        let (pin, sm, installed) = self.uninit();
+        program.release(pio, installed);
+        (pin, sm)
+    }
 
+    /// 1回のオートプルで取り出すビット数を指定して初期化します。
+    fn with_pull_threshold(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        timing: Timing,
+        pull_threshold: u8,
+    ) -> Self {
+        let installed = install(pio, timing);
+        Self::with_program(pin, installed, sm, clock_freq, timing, pull_threshold)
+    }
+
+    fn with_program(
+        pin: I,
+        installed: InstalledProgram<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        timing: Timing,
+        pull_threshold: u8,
+    ) -> Self {
         let pin = pin.into();
-        let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
-            .buffers(Buffers::OnlyTx)
-            .side_set_pin_base(pin.id().num)
-            .out_shift_direction(ShiftDirection::Left)
-            .autopull(true)
-            .pull_threshold(24)
-            .clock_divisor_fixed_point(int, frac)
-            .build(sm);
+        let (mut sm, rx, tx) = build_state_machine(
+            installed,
+            sm,
+            clock_freq,
+            timing,
+            pin.id().num,
+            pull_threshold,
+        );
         sm.set_pindirs([(pin.id().num, PinDir::Output)]);
-        sm.start();
+        let sm = sm.start();
 
         Self {
             tx,
-            _pin: I::from(pin),
+            parts: Parts {
+                sm,
+                rx,
+                pin: I::from(pin),
+                format: PixelFormat::Grb,
+                timing,
+                reset_micros: RESET_MICROS,
+                word_micros: (pull_threshold as u32 * 1_000_000)
+                    .div_ceil(timing.bit_rate().to_Hz()),
+                length: None,
+                brightness: 255,
+                dimming: DimmingCurve::Linear,
+                shared: false,
+                checking: false,
+                underrun: false,
+                waiter: Waiter::default(),
+                stats: Stats::default(),
+            },
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn uninit(self) -> (I, UninitStateMachine<(P, SM)>, InstalledProgram<P>) {
+        let Parts { sm, rx, pin, .. } = self.parts;
+        let (sm, installed) = sm.uninit(rx, self.tx);
+        (pin, sm, installed)
+    }
+
+    /// 明るさの曲線を通した全体の明るさを返します。
+    pub(crate) fn fraction(&self) -> u8 {
+        self.parts.dimming.apply(self.parts.brightness)
+    }
+
+    /// 1ワードを送るのにかかる時間(µs、切り上げ)を返します。
+    pub(crate) fn word_micros(&self) -> u32 {
+        self.parts.word_micros
+    }
+
+    /// FIFOが空で、ステートマシンが最後のビットまで送り終えているかを返します。
+    ///
+    /// リセット時間が経ったかは分からないので、[`Ws2812::is_idle`]も参照してください。
+    ///
+    /// ストールのフラグはフレームを書き込み始めたときに消すので、
+    /// ステートマシンが空のFIFOで止まるとフレームの最後まで送り終えたことが分かります。
+    pub fn is_idle(&self) -> bool {
+        self.tx.is_empty() && self.tx.has_stalled()
+    }
+
+    /// [`Ws2812Direct::is_idle`]の逆を返します。
+    pub fn is_busy(&self) -> bool {
+        !self.is_idle()
+    }
+
+    /// FIFOが空になり、ステートマシンが最後のビットまで送り終えるまで待ちます。
+    ///
+    /// リセット時間は待たないので、ストリップの電源を切る前などには
+    /// この後にリセット時間だけ待つか、[`Ws2812::flush`]を使ってください。
+    pub fn wait_idle(&self) {
+        self.tx.clear_stalled_flag();
+        while !self.is_idle() {
+            self.wait();
+        }
+    }
+
+    /// FIFOが空くのを待つ間に繰り返し呼ぶ関数を設定します。
+    ///
+    /// 長いストリップへ書き込んでいる間もハードウェアのウォッチドッグに餌をやれるので、
+    /// フレームの途中でリセットされるのを防げます。
+    /// FIFOの1ワード分の時間より十分短く終わる関数にしてください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.parts.waiter.set_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。`None`を渡すとループで待ちます。
+    ///
+    /// PIOの`irq`の"TX not full"割り込みを待つ間だけ有効にし、
+    /// NVICで無効な割り込みでも起きられるようにSCRのSEVONPENDを設定します。
+    /// NVICで`irq`の割り込みを有効にしている場合は使わないでください。
+    /// 電池で動かす場合に、送信中の消費電力を減らせます。
+    /// [`Ws2812Direct::set_wait_hook`]を設定している場合はそちらを優先します。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.parts.waiter.set_irq(irq);
+    }
+
+    /// FIFOが空くのを待つ間の1回分の処理をします。
+    pub(crate) fn wait(&self) {
+        self.parts.waiter.wait(&self.tx)
+    }
+
+    /// 最後に`write`したフレームの途中でFIFOが空になったかを返します。
+    ///
+    /// FIFOが空になるとデータ線がLowのまま止まり、途中までのフレームがラッチされて
+    /// 残りのLEDがずれた色で表示されることがあります。
+    /// イテレーターの計算が間に合っていないので、フレームを先にバッファに描いてください。
+    pub fn underrun(&self) -> bool {
+        self.parts.underrun
+    }
+
+    /// 送っている途中のフレームを捨てます。
+    ///
+    /// FIFOを空にしてステートマシンを最初からやり直すので、データ線はすぐにLowになります。
+    /// 次のフレームはリセット時間が経ってから書き込んでください。
+    pub fn abort(&mut self) {
+        self.parts.sm.clear_fifos();
+        self.parts.sm.restart();
+    }
+
+    /// FIFOが空になり、ステートマシンがストールするまで待ちます。
+    fn wait_drained(&self) {
+        self.tx.clear_stalled_flag();
+        while !self.is_idle() {
+            self.wait();
         }
     }
 }
 
-impl<P, SM, I> SmartLedsWrite for Ws2812Direct<P, SM, I>
+impl<P, SM, I> Ws2812Direct<P, SM, I>
 where
     I: AnyPin<Function = P::PinFunction>,
     P: PIOExt,
     SM: StateMachineIndex,
 {
-    type Color = RGB8;
-    type Error = ();
-    fn write<T, C>(&mut self, iterator: T) -> Result<(), ()>
+    /// タイマーのカウントが`at`になった瞬間にフレームを送り始めます。
+    ///
+    /// ステートマシンを止めてFIFOを先に埋めておき、`at`になったら動かすので、
+    /// 別のコントローラーやカメラなどと1µsの精度で揃えられます。
+    /// 前のフレームを送り終えるまで待ってから準備するので、リセット時間は呼び出し側で確保してください。
+    /// `at`が過ぎていた場合はすぐに送り始めます。
+    pub fn write_at<D, T, C>(
+        &mut self,
+        at: Instant,
+        timer: &Timer<D>,
+        iterator: T,
+    ) -> Result<(), Error>
     where
+        D: TimerDevice,
         T: IntoIterator<Item = C>,
-        C: Into<Self::Color>,
+        C: Into<RGB8>,
     {
-        for item in iterator {
-            let color: Self::Color = item.into();
-            let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
-            let word: u32 = g << 24 | r << 16 | b << 8;
+        let mut frame = self.start_frame(Feeder::new(iterator.into_iter()))?;
+        self.wait_idle();
+
+        let mask = 1u32 << SM::id();
+        let ctrl = pio_regs::<P>().ctrl().as_ptr() as usize;
+        // Safety: アトミックなクリア用のエイリアスなので、他のステートマシンのビットには影響しません。
+        unsafe { ((ctrl + 0x3000) as *mut u32).write_volatile(mask) };
+        frame.feeder.feed(self, frame.limit);
+        while timer.get_counter() < at {}
+        // Safety: アトミックなセット用のエイリアスなので、同上です。
+        unsafe { ((ctrl + 0x2000) as *mut u32).write_volatile(mask) };
 
+        self.feed_frame(frame)
+    }
+
+    /// 変換済みのワードをそのままFIFOに書き込みます。
+    ///
+    /// 色の変換や明るさ、ストリップの長さの設定を通さないので、
+    /// ビルド時に[`PixelFormat::encode`]と同じ形式へ変換しておいたフレームを速く送れます。
+    pub fn write_raw(&mut self, words: &[u32]) {
+        self.write_raw_iter(words.iter().copied());
+    }
+
+    /// 変換済みのワードを返すイテレーターからFIFOに書き込みます。[`Ws2812Direct::write_raw`]を参照してください。
+    pub fn write_raw_iter<T>(&mut self, words: T)
+    where
+        T: IntoIterator<Item = u32>,
+    {
+        for (i, word) in words.into_iter().enumerate() {
             while !self.tx.write(word) {
-                cortex_m::asm::nop();
+                self.wait();
             }
+            if i == 0 {
+                self.tx.clear_stalled_flag();
+            }
+        }
+    }
+
+    /// 書き込めるピクセル数の上限を返します。
+    ///
+    /// `ExactSizeIterator`のように長さが分かるイテレーターで、ストリップの長さを超える場合は
+    /// 何も送らないうちに[`Error::LengthMismatch`]を返します。
+    fn frame_limit<T: Iterator>(&self, iter: &T) -> Result<usize, Error> {
+        match self.parts.length {
+            Some(length) if iter.size_hint().0 > length => Err(Error::LengthMismatch),
+            Some(length) => Ok(length),
+            None => Ok(usize::MAX),
         }
-        Ok(())
+    }
+
+    /// フレームを書き込み始めます。
+    ///
+    /// 長さの分かるイテレーターがストリップより長い場合は[`Error::LengthMismatch`]を返します。
+    pub(crate) fn start_frame<T, E>(
+        &mut self,
+        mut feeder: Feeder<T, E>,
+    ) -> Result<Frame<T, E>, Error>
+    where
+        T: Iterator,
+        T::Item: Into<E::Color>,
+        E: Encoding,
+    {
+        let limit = self.frame_limit(feeder.iter_mut())?;
+        self.parts.underrun = false;
+        self.parts.checking = false;
+        Ok(Frame::new(feeder, limit))
+    }
+
+    /// フレームの残りを書き込んで統計を更新します。
+    pub(crate) fn feed_frame<T, E>(&mut self, mut frame: Frame<T, E>) -> Result<(), Error>
+    where
+        T: Iterator,
+        T::Item: Into<E::Color>,
+        E: Encoding,
+    {
+        loop {
+            if let Some(result) = self.feed_step(&mut frame, usize::MAX) {
+                return result;
+            }
+            self.wait();
+        }
+    }
+
+    /// FIFOが一杯になるか、`chunk`ピクセル書き込むまでフレームの続きを書き込みます。
+    ///
+    /// 短いフレームの後ろはストリップの長さまで消灯のデータで埋めます。
+    /// 最初のワードを書き込んだ後はFIFOが空にならないかを調べ、
+    /// フレームを書き終えたら統計を更新して結果を返します。
+    pub(crate) fn feed_step<T, E>(
+        &mut self,
+        frame: &mut Frame<T, E>,
+        chunk: usize,
+    ) -> Option<Result<(), Error>>
+    where
+        T: Iterator,
+        T::Item: Into<E::Color>,
+        E: Encoding,
+    {
+        if !frame.started {
+            // 最初のワードは止まっているステートマシンに書き込むので、2ワード目から調べます。
+            let done = frame.limit == 0 || frame.feeder.feed(self, 1);
+            if !done && frame.feeder.written() == 0 {
+                return None;
+            }
+            self.tx.clear_stalled_flag();
+            self.parts.checking = true;
+            frame.started = true;
+        }
+
+        let black = match &mut frame.black {
+            Some(black) => black,
+            None => {
+                let written = frame.feeder.written();
+                let end = written.saturating_add(chunk).min(frame.limit);
+                if !frame.feeder.feed(self, end) {
+                    if frame.feeder.written() < frame.limit {
+                        return None;
+                    }
+                    let result = if frame.feeder.has_more() {
+                        Err(Error::LengthMismatch)
+                    } else {
+                        Ok(())
+                    };
+                    return Some(self.finish_frame(frame.limit, result));
+                }
+                let pixels = frame.feeder.written();
+                let count = self
+                    .parts
+                    .length
+                    .map_or(0, |length| length.saturating_sub(pixels));
+                frame.black.insert(frame.feeder.black(count))
+            }
+        };
+        if !black.feed(self, black.written().saturating_add(chunk)) {
+            return None;
+        }
+        let pixels = frame.feeder.written() + black.written();
+        Some(self.finish_frame(pixels, Ok(())))
+    }
+
+    /// FIFOが空になったかを調べるのをやめて、統計を更新します。
+    fn finish_frame(&mut self, pixels: usize, result: Result<(), Error>) -> Result<(), Error> {
+        self.parts.checking = false;
+        let stats = &mut self.parts.stats;
+        stats.frames = stats.frames.wrapping_add(1);
+        stats.pixels = stats.pixels.wrapping_add(pixels as u32);
+        if self.parts.underrun {
+            stats.underruns = stats.underruns.wrapping_add(1);
+        }
+        result
+    }
+
+    /// 送ったフレームなどの数を返します。
+    pub fn stats(&self) -> &Stats {
+        &self.parts.stats
+    }
+
+    /// 統計を0に戻します。
+    pub fn reset_stats(&mut self) {
+        self.parts.stats = Stats::default();
+    }
+}
+
+impl<P, SM, I> SmartLedsWrite for Ws2812Direct<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let frame = self.start_frame(Feeder::new(iterator.into_iter()))?;
+        self.feed_frame(frame)
     }
 }
 
@@ -130,6 +936,28 @@ where
 {
     cd: CountDown<'timer, D>,
     driver: Ws2812Direct<P, SM, I>,
+    /// 明るさのフェードと、その時刻を読むタイマー
+    fade: Option<(Fade, Timer<D>)>,
+    /// `cd`で前のフレームのラッチを待っている
+    latching: bool,
+    /// フレームの送信時間を測るタイマー
+    clock: Option<Timer<D>>,
+    /// 送信時間を測っているフレームを書き込み始めた時刻
+    frame_start: Option<Instant>,
+    /// 最後に測ったフレームの送信時間
+    frame_time: Option<MicrosDurationU32>,
+    /// フレームを書き込む最短の間隔と、その時刻を読むタイマー
+    pacing: Option<(MicrosDurationU32, Timer<D>)>,
+    /// 最後にフレームを書き込み始めた時刻
+    last_write: Option<Instant>,
+    /// フレームを書き込んだ間隔
+    intervals: IntervalStats,
+    /// 1フレームにかけてよい時間
+    budget: Option<MicrosDurationU32>,
+    /// `budget`を超えたときに呼ぶ関数
+    deadline_hook: Option<fn()>,
+    /// 最後のフレームが`budget`を超えた
+    deadline_missed: bool,
 }
 
 impl<'timer, D, P, SM, I> Ws2812<'timer, D, P, SM, I>
@@ -148,7 +976,259 @@ where
         cd: CountDown<'timer, D>,
     ) -> Ws2812<'timer, D, P, SM, I> {
         let driver = Ws2812Direct::new(pin, pio, sm, clock_freq);
-        Self { driver, cd }
+        Self::from_driver(driver, cd)
+    }
+
+    /// `chip`のタイミング、リセット時間、データの並びで送るように初期化します。
+    pub fn with_chip(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        cd: CountDown<'timer, D>,
+        chip: Chip,
+    ) -> Ws2812<'timer, D, P, SM, I> {
+        let driver = Ws2812Direct::with_chip(pin, pio, sm, clock_freq, chip);
+        Self::from_driver(driver, cd)
+    }
+
+    /// `config`のタイミング、リセット時間、データの並びで送るように初期化します。
+    pub fn with_config(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        cd: CountDown<'timer, D>,
+        config: Config,
+    ) -> Ws2812<'timer, D, P, SM, I> {
+        let driver = Ws2812Direct::with_config(pin, pio, sm, clock_freq, config);
+        Self::from_driver(driver, cd)
+    }
+
+    /// 初期化したドライバとカウントダウンからまとめます。
+    fn from_driver(driver: Ws2812Direct<P, SM, I>, cd: CountDown<'timer, D>) -> Self {
+        Self {
+            driver,
+            cd,
+            fade: None,
+            latching: false,
+            clock: None,
+            frame_start: None,
+            frame_time: None,
+            pacing: None,
+            last_write: None,
+            intervals: IntervalStats::new(),
+            budget: None,
+            deadline_hook: None,
+            deadline_missed: false,
+        }
+    }
+
+    /// ストリップのLEDの数を設定します。[`Ws2812Direct::set_length`]を参照してください。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.driver.set_length(length);
+    }
+
+    /// `fps`で更新し続けられるLEDの最大の数を返します。[`Ws2812Direct::max_leds`]を参照してください。
+    pub fn max_leds(&self, fps: u32) -> usize {
+        self.driver.max_leds(fps)
+    }
+
+    /// `fps`のフレームレートを出せるかを調べます。[`Ws2812Direct::check_frame_rate`]を参照してください。
+    pub fn check_frame_rate(&self, fps: u32) -> Result<(), Error> {
+        self.driver.check_frame_rate(fps)
+    }
+
+    /// 全体の明るさを設定します。[`Ws2812Direct::set_brightness`]を参照してください。
+    ///
+    /// フェードの途中だった場合はフェードを止めます。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.fade = None;
+        self.driver.set_brightness(brightness);
+    }
+
+    /// 全体の明るさを`duration`かけて`target`に変えます。
+    ///
+    /// 明るさはこの後に書き込むフレームごとに更新されます。
+    /// `timer`は`cd`を作ったタイマーを渡してください。
+    pub fn fade_brightness_to(
+        &mut self,
+        target: u8,
+        duration: MicrosDurationU32,
+        timer: &Timer<D>,
+    ) {
+        let fade = Fade::new(
+            self.driver.brightness(),
+            target,
+            timer.get_counter(),
+            duration,
+        );
+        self.fade = Some((fade, *timer));
+    }
+
+    /// ラッチに必要なリセット時間(µs)を設定します。[`Ws2812Direct::set_reset_micros`]を参照してください。
+    pub fn set_reset_micros(&mut self, reset_micros: u32) {
+        self.driver.set_reset_micros(reset_micros);
+    }
+
+    /// 全体の明るさの曲線を設定します。[`Ws2812Direct::set_dimming_curve`]を参照してください。
+    pub fn set_dimming_curve(&mut self, curve: DimmingCurve) {
+        self.driver.set_dimming_curve(curve);
+    }
+
+    /// 明るさのフェードの途中かを返します。
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// フレームレートが`fps`を超えないように、`write`の間隔を空けます。
+    ///
+    /// 前のフレームを書き込み始めてから`1 / fps`秒経つまで、次の`write`は待ちます。
+    /// 誤って何kHzもの速さで書き込み続け、LEDや電源に負担をかけるのを防げます。
+    pub fn set_max_fps(&mut self, fps: u32, timer: &Timer<D>) {
+        self.pacing = Some(((1_000_000 / fps.max(1)).micros(), *timer));
+    }
+
+    /// [`Ws2812::set_max_fps`]の制限を外します。
+    pub fn clear_max_fps(&mut self) {
+        self.pacing = None;
+        self.last_write = None;
+    }
+
+    /// `timer`でフレームの送信時間と書き込みの間隔を測るようにします。
+    ///
+    /// 測った時間は[`Ws2812::frame_time`]と[`Ws2812::intervals`]で読めます。
+    pub fn measure_with(&mut self, timer: &Timer<D>) {
+        self.clock = Some(*timer);
+    }
+
+    /// 最後のフレームの送信時間を返します。
+    ///
+    /// `write`の後はFIFOに残ったワードの分を見積もった時間で、
+    /// `flush`の後は最後のビットを送り終えるまでを測った時間です。
+    /// [`Ws2812::measure_with`]を呼んでいない場合は`None`を返します。
+    pub fn frame_time(&self) -> Option<MicrosDurationU32> {
+        self.frame_time
+    }
+
+    /// 1フレームの色の変換と送信にかけてよい時間を設定します。
+    ///
+    /// [`Ws2812::frame_time`]が`budget`を超えると[`Ws2812::deadline_missed`]が`true`になり、
+    /// `hook`を渡していればその場で呼びます。`None`を渡すと調べるのをやめます。
+    /// 時間を測るので、[`Ws2812::measure_with`]も呼んでください。
+    pub fn set_frame_budget(&mut self, budget: Option<MicrosDurationU32>, hook: Option<fn()>) {
+        self.budget = budget;
+        self.deadline_hook = hook;
+        self.deadline_missed = false;
+    }
+
+    /// 最後のフレームが[`Ws2812::set_frame_budget`]の時間を超えたかを返します。
+    pub fn deadline_missed(&self) -> bool {
+        self.deadline_missed
+    }
+
+    /// フレームを書き込んだ間隔の統計を返します。
+    ///
+    /// [`Ws2812::measure_with`]を呼んでからの`write`の間隔を記録します。
+    pub fn intervals(&self) -> &IntervalStats {
+        &self.intervals
+    }
+
+    /// フレームを書き込んだ間隔の統計を消します。
+    pub fn reset_intervals(&mut self) {
+        self.intervals.reset();
+    }
+
+    /// 前のフレームを送り終え、リセット時間が経っているかを返します。
+    ///
+    /// `true`の間は`write`を呼んでもすぐに送り始められます。
+    pub fn is_idle(&mut self) -> bool {
+        if !self.driver.is_idle() {
+            return false;
+        }
+        if self.latching {
+            if self.cd.wait().is_err() {
+                return false;
+            }
+            self.latching = false;
+        }
+        true
+    }
+
+    /// 前のフレームを送っているか、リセット時間を待っているかを返します。
+    pub fn is_busy(&mut self) -> bool {
+        !self.is_idle()
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。[`Ws2812Direct::set_wait_hook`]を参照してください。
+    ///
+    /// 前のフレームのリセット時間を待つ間にも呼びます。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.driver.set_wait_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。[`Ws2812Direct::set_wait_for_event`]を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.driver.set_wait_for_event(irq);
+    }
+
+    /// 送ったフレームなどの数を返します。[`Ws2812Direct::stats`]を参照してください。
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    /// 最後のフレームの途中でFIFOが空になったかを返します。[`Ws2812Direct::underrun`]を参照してください。
+    pub fn underrun(&self) -> bool {
+        self.driver.underrun()
+    }
+
+    /// 送っている途中のフレームを捨てます。[`Ws2812Direct::abort`]を参照してください。
+    ///
+    /// 次の`write`はリセット時間が経つまで待ってから送り始めます。
+    pub fn abort(&mut self) {
+        self.driver.abort();
+        self.cd.start(self.driver.reset_micros().micros());
+        self.latching = true;
+        self.frame_start = None;
+    }
+
+    /// 書き込んだフレームを送り終え、リセット時間が経つまで待ちます。
+    ///
+    /// ストリップの電源を切る前や、スリープに入る前、ピンを設定し直す前に呼んでください。
+    pub fn flush(&mut self) {
+        self.driver.wait_idle();
+        if let (Some(timer), Some(start)) = (&self.clock, self.frame_start.take()) {
+            self.frame_time = Some(elapsed_micros(timer, start));
+        }
+        if self.latching {
+            while self.cd.wait().is_err() {
+                self.driver.wait();
+            }
+            self.latching = false;
+        }
+    }
+
+    /// フレームを書き込み、途中でFIFOが空になった場合は最大`retries`回送り直します。
+    ///
+    /// 送り直すためにイテレーターを複製するので、複製しても同じ色を返すイテレーターを渡してください。
+    /// 送り直すときも前のフレームのリセット時間が経つまで待ちます。
+    /// 最後まで送り直せなかった場合は[`Ws2812::underrun`]が`true`のままになります。
+    pub fn write_retrying<T, J>(&mut self, iterator: T, retries: u8) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        T::IntoIter: Clone,
+        J: Into<RGB8>,
+    {
+        let iter = iterator.into_iter();
+        for _ in 0..retries {
+            SmartLedsWrite::write(self, iter.clone())?;
+            if !self.underrun() {
+                return Ok(());
+            }
+            let stats = &mut self.driver.parts.stats;
+            stats.retries = stats.retries.wrapping_add(1);
+        }
+        SmartLedsWrite::write(self, iter)
     }
 }
 
@@ -160,18 +1240,110 @@ where
     SM: StateMachineIndex,
 {
     type Color = RGB8;
-    type Error = ();
-    fn write<T, J>(&mut self, iterator: T) -> Result<(), ()>
+    type Error = Error;
+    fn write<T, J>(&mut self, iterator: T) -> Result<(), Error>
     where
         T: IntoIterator<Item = J>,
         J: Into<Self::Color>,
     {
-        self.driver.tx.clear_stalled_flag();
-        while !self.driver.tx.is_empty() && !self.driver.tx.has_stalled() {}
+        self.write_with(None, |driver| SmartLedsWrite::write(driver, iterator))
+    }
+}
+
+impl<'timer, D, P, SM, I> Ws2812<'timer, D, P, SM, I>
+where
+    D: TimerDevice,
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// タイマーのカウントが`at`になった瞬間にフレームを送り始めます。
+    ///
+    /// [`Ws2812Direct::write_at`]を参照してください。
+    /// `at`が前のフレームのリセット時間の途中だった場合は、リセット時間が経ってから送り始めます。
+    pub fn write_at<T, J>(
+        &mut self,
+        at: Instant,
+        timer: &Timer<D>,
+        iterator: T,
+    ) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<RGB8>,
+    {
+        self.write_with(Some(at), |driver| driver.write_at(at, timer, iterator))
+    }
+
+    /// 変換済みのワードをそのまま送ります。[`Ws2812Direct::write_raw`]を参照してください。
+    pub fn write_raw(&mut self, words: &[u32]) {
+        self.write_raw_iter(words.iter().copied());
+    }
 
-        self.cd.start(70_u32.micros());
-        let _ = nb::block!(self.cd.wait());
+    /// 変換済みのワードを返すイテレーターから送ります。[`Ws2812Direct::write_raw`]を参照してください。
+    pub fn write_raw_iter<T>(&mut self, words: T)
+    where
+        T: IntoIterator<Item = u32>,
+    {
+        let _ = self.write_with(None, |driver| {
+            driver.write_raw_iter(words);
+            Ok(())
+        });
+    }
+
+    /// 明るさのフェードを進め、前のフレームのラッチを待ってから`send`でフレームを送ります。
+    fn write_with<F>(&mut self, at: Option<Instant>, send: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Ws2812Direct<P, SM, I>) -> Result<(), Error>,
+    {
+        if let Some((period, timer)) = &self.pacing {
+            if let Some(last) = self.last_write {
+                while elapsed_micros(timer, last) < *period {
+                    self.driver.wait();
+                }
+            }
+            self.last_write = Some(timer.get_counter());
+        }
 
-        SmartLedsWrite::write(&mut self.driver, iterator)
+        if let Some((fade, timer)) = &self.fade {
+            let now = timer.get_counter();
+            self.driver.set_brightness(fade.level(now));
+            if fade.is_done(now) {
+                self.fade = None;
+            }
+        }
+
+        // 前のフレームの後に他の処理で時間が経っていれば、残りのリセット時間だけ待ちます。
+        if self.latching {
+            while self.cd.wait().is_err() {
+                self.driver.wait();
+            }
+        }
+
+        let start = self.clock.map(|timer| {
+            timer
+                .get_counter()
+                .max(at.unwrap_or(Instant::from_ticks(0)))
+        });
+        if let Some(start) = start {
+            self.intervals.record(start);
+        }
+        let result = send(&mut self.driver);
+
+        // FIFOとOSRに残っているワードを送り終えてから、リセット時間が経つまでを測ります。
+        let drain = DRAIN_WORDS * self.driver.word_micros();
+        if let (Some(timer), Some(start)) = (&self.clock, start) {
+            let frame_time = elapsed_micros(timer, start) + drain.micros();
+            self.frame_time = Some(frame_time);
+            self.frame_start = Some(start);
+            if let Some(budget) = self.budget {
+                self.deadline_missed = frame_time > budget;
+                if let (true, Some(hook)) = (self.deadline_missed, self.deadline_hook) {
+                    hook();
+                }
+            }
+        }
+        self.cd.start((drain + self.driver.reset_micros()).micros());
+        self.latching = true;
+        result
     }
 }
@@ -0,0 +1,154 @@
+//! 同じデータを複数のピンへ同時に出力するドライバです。
+//!
+//! `set pins`で連続した最大5本のピンを一斉に切り替えるので、
+//! 離れた場所にある同じ内容のストリップを、データを二度送らずに完全に同期させられます。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{Buffers, PIOBuilder, PinDir, ShiftDirection},
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, Tx, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, Strip, Waiter, clock_divisor, feed::Feeder};
+
+/// 同じデータを`N`本のピンへ出力するWS2812ドライバ
+///
+/// フレーム間のリセット時間(50µs以上)は呼び出し側で確保してください。
+pub struct Ws2812Mirror<P, SM, I, const N: usize>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    tx: Tx<(P, SM)>,
+    _pins: [I; N],
+    waiter: Waiter,
+}
+
+impl<P, SM, I, const N: usize> Ws2812Mirror<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// `pins`はGPIO番号が1つずつ増えるように並べてください。
+    ///
+    /// 型を揃えるために、ピンは`into_dyn_pin`などで変換しておく必要があります。
+    pub fn new(
+        pins: [I; N],
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        const T1: u8 = 2; // start bit
+        const T2: u8 = 5; // data bit
+        const T3: u8 = 3; // stop bit
+        const CYCLES_PER_BIT: u32 = (T1 + T2 + T3) as u32;
+
+        assert!((1..=5).contains(&N), "1 to 5 pins are supported.");
+
+        // PIOに入れるプログラム
+        let program = pio_proc::pio_asm!(
+            ".define public T1 2",
+            ".define public T2 5",
+            ".define public T3 3",
+            ".wrap_target",
+            "bitloop:",
+            "    set pins, 0        [T3 - 2]",
+            "    out x, 1",
+            "    set pins, 31       [T1 - 2]",
+            "    jmp !x do_zero",
+            "    jmp bitloop        [T2 - 1]",
+            "do_zero:",
+            "    set pins, 0        [T2 - 1]",
+            ".wrap",
+        );
+
+        let installed = pio.install(&program.program).unwrap();
+
+        let (int, frac) = clock_divisor(clock_freq, HertzU32::kHz(800), CYCLES_PER_BIT);
+
+        let pins = pins.map(|pin| pin.into());
+        let base = pins[0].id().num;
+        for (i, pin) in pins.iter().enumerate() {
+            assert_eq!(
+                pin.id().num,
+                base + i as u8,
+                "pins must be consecutive GPIOs"
+            );
+        }
+        let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+            .buffers(Buffers::OnlyTx)
+            .set_pins(base, N as u8)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(24)
+            .clock_divisor_fixed_point(int, frac)
+            .build(sm);
+        sm.set_pindirs(pins.iter().map(|pin| (pin.id().num, PinDir::Output)));
+        sm.start();
+
+        Self {
+            tx,
+            _pins: pins.map(I::from),
+            waiter: Waiter::default(),
+        }
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。
+    /// [`Ws2812Direct::set_wait_hook`](crate::Ws2812Direct::set_wait_hook)を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.waiter.set_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。
+    /// [`Ws2812Direct::set_wait_for_event`](crate::Ws2812Direct::set_wait_for_event)を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.waiter.set_irq(irq);
+    }
+}
+
+impl<P, SM, I, const N: usize> Strip for Ws2812Mirror<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    fn write_word(&mut self, word: u32) -> bool {
+        self.tx.write(word)
+    }
+
+    fn wait(&self) {
+        self.waiter.wait(&self.tx);
+    }
+
+    fn wait_drained(&self) {
+        self.tx.clear_stalled_flag();
+        while !(self.tx.is_empty() && self.tx.has_stalled()) {
+            self.wait();
+        }
+    }
+}
+
+impl<P, SM, I, const N: usize> SmartLedsWrite for Ws2812Mirror<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let mut feeder = Feeder::new(iterator.into_iter());
+        while !feeder.feed(self, usize::MAX) {
+            self.wait();
+        }
+        Ok(())
+    }
+}
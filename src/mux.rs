@@ -0,0 +1,159 @@
+//! 1つのステートマシンで複数のストリップを順番に駆動するドライバです。
+//!
+//! フレームの合間にステートマシンのサイドセットのピンを付け替えるので、
+//! ステートマシンが足りないけれど、更新頻度の低いストリップがたくさんある場合に使えます。
+//! 選ばれていないピンはLowのまま保たれます。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, PinDir, PioIRQ, StateMachineIndex, Tx, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, Strip, Timing, Waiter, build_state_machine, feed::Feeder, install, pio_regs};
+
+/// SM_PINCTRLのSIDESET_BASEの位置
+const SIDESET_BASE: u32 = 10;
+
+/// `N`本のストリップを切り替えながら駆動するWS2812ドライバ
+///
+/// ピンを切り替えずに同じストリップへ続けて書き込む場合、
+/// フレーム間のリセット時間(50µs以上)は呼び出し側で確保してください。
+pub struct Ws2812Mux<P, SM, I, const N: usize>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    tx: Tx<(P, SM)>,
+    _pins: [I; N],
+    nums: [u8; N],
+    selected: usize,
+    waiter: Waiter,
+    /// 1µsあたりのシステムクロックのサイクル数
+    cycles_per_micro: u32,
+}
+
+impl<P, SM, I, const N: usize> Ws2812Mux<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// 型を揃えるために、ピンは`into_dyn_pin`などで変換しておく必要があります。
+    ///
+    /// 最初は`pins[0]`のストリップが選ばれています。
+    pub fn new(
+        pins: [I; N],
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        assert!(N > 0, "at least one pin is required");
+        let pins = pins.map(|pin| pin.into());
+        let nums = pins.each_ref().map(|pin| pin.id().num);
+        let installed = install(pio, Timing::WS2812);
+        let (mut sm, _, tx) =
+            build_state_machine(installed, sm, clock_freq, Timing::WS2812, nums[0], 24);
+        sm.set_pindirs(nums.map(|num| (num, PinDir::Output)));
+        sm.start();
+
+        Self {
+            tx,
+            _pins: pins.map(I::from),
+            nums,
+            selected: 0,
+            waiter: Waiter::default(),
+            cycles_per_micro: clock_freq.to_MHz(),
+        }
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。
+    /// [`Ws2812Direct::set_wait_hook`](crate::Ws2812Direct::set_wait_hook)を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.waiter.set_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。
+    /// [`Ws2812Direct::set_wait_for_event`](crate::Ws2812Direct::set_wait_for_event)を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.waiter.set_irq(irq);
+    }
+
+    /// 選ばれているストリップの番号を返します。
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// 前のフレームを送り終え、リセット時間が経つまで待ってから、出力先を`strip`番目のピンに切り替えます。
+    pub fn select(&mut self, strip: usize) {
+        assert!(strip < N, "strip index out of range");
+        if strip == self.selected {
+            return;
+        }
+        self.wait_drained();
+        // 前のストリップがラッチする前に切り替えないように、リセット時間だけ待ちます。
+        cortex_m::asm::delay(self.reset_micros() * self.cycles_per_micro);
+        let flip = (self.nums[self.selected] ^ self.nums[strip]) as u32;
+        let pinctrl = pio_regs::<P>().sm(SM::id()).sm_pinctrl().as_ptr() as usize;
+        // Safety: アトミックな反転用のエイリアスで、SIDESET_BASEの異なるビットだけを1回で書き換えます。
+        unsafe { ((pinctrl + 0x1000) as *mut u32).write_volatile(flip << SIDESET_BASE) };
+        self.selected = strip;
+    }
+
+    /// `strip`番目のストリップへ書き込みます。
+    pub fn write_to<T, C>(&mut self, strip: usize, iterator: T)
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<RGB8>,
+    {
+        self.select(strip);
+        let _ = SmartLedsWrite::write(self, iterator);
+    }
+}
+
+impl<P, SM, I, const N: usize> Strip for Ws2812Mux<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    fn write_word(&mut self, word: u32) -> bool {
+        self.tx.write(word)
+    }
+
+    fn wait(&self) {
+        self.waiter.wait(&self.tx);
+    }
+
+    fn wait_drained(&self) {
+        self.tx.clear_stalled_flag();
+        // FIFOが空になってもOSRのワードを送っている間はストールしないので、両方を待ちます。
+        while !(self.tx.is_empty() && self.tx.has_stalled()) {
+            self.wait();
+        }
+    }
+}
+
+impl<P, SM, I, const N: usize> SmartLedsWrite for Ws2812Mux<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB8;
+    type Error = Error;
+    /// 選ばれているストリップへ書き込みます。
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let mut feeder = Feeder::new(iterator.into_iter());
+        while !feeder.feed(self, usize::MAX) {
+            self.wait();
+        }
+        Ok(())
+    }
+}
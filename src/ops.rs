@@ -0,0 +1,55 @@
+//! フレーム同士の演算です。
+//!
+//! レイヤーの合成やトランジションで使う演算を、フレームバッファの上でまとめて行います。
+//! 長さの違うフレームを渡した場合は短い方に合わせます。
+
+use smart_leds_trait::RGB8;
+
+/// 2つの色のチャンネルごとに`f`を適用します。
+#[inline(always)]
+fn zip_with(a: RGB8, b: RGB8, f: impl Fn(u8, u8) -> u8) -> RGB8 {
+    RGB8::new(f(a.r, b.r), f(a.g, b.g), f(a.b, b.b))
+}
+
+/// `dst`に`src`を足し合わせます(255で飽和)。
+pub fn add(dst: &mut [RGB8], src: &[RGB8]) {
+    for (dst, &src) in dst.iter_mut().zip(src) {
+        *dst = zip_with(*dst, src, u8::saturating_add);
+    }
+}
+
+/// `dst`から`src`を引きます(0で飽和)。
+pub fn sub(dst: &mut [RGB8], src: &[RGB8]) {
+    for (dst, &src) in dst.iter_mut().zip(src) {
+        *dst = zip_with(*dst, src, u8::saturating_sub);
+    }
+}
+
+/// `dst`を`dst`と`src`の明るい方にします。
+pub fn max(dst: &mut [RGB8], src: &[RGB8]) {
+    for (dst, &src) in dst.iter_mut().zip(src) {
+        *dst = zip_with(*dst, src, u8::max);
+    }
+}
+
+/// 全てのピクセルを(`fraction` + 1)/256倍にします(切り捨て)。
+///
+/// 255は等倍、0は消灯です。
+pub fn scale(frame: &mut [RGB8], fraction: u8) {
+    for pixel in frame {
+        *pixel = scale_pixel(*pixel, fraction);
+    }
+}
+
+/// 1つのピクセルを(`fraction` + 1)/256倍にします。255は等倍です。
+#[inline(always)]
+pub(crate) fn scale_pixel(color: RGB8, fraction: u8) -> RGB8 {
+    let mul = |v: u8| scale_channel(v, fraction);
+    RGB8::new(mul(color.r), mul(color.g), mul(color.b))
+}
+
+/// 1つのチャンネルを(`fraction` + 1)/256倍にします。255は等倍です。
+#[inline(always)]
+pub(crate) fn scale_channel(value: u8, fraction: u8) -> u8 {
+    ((value as u16 * (fraction as u16 + 1)) >> 8) as u8
+}
@@ -0,0 +1,120 @@
+//! ピクセルを隙間なく詰めてFIFOへ書き込むドライバです。
+//!
+//! 24bitのオートプルでは、FIFOの1ワードのうち8bitが使われません。
+//! このドライバは32bitでオートプルして、4ピクセル(96bit)を3ワードに詰めるので、
+//! FIFOへの書き込みとDMAの転送量が25%減ります。
+//!
+//! ピクセルの数が4の倍数でない場合、最後のワードだけオートプルの閾値を残りのビット数に縮めるので、
+//! フレームの後ろに余分なビットは送りません。
+//! 閾値はステートマシンが止まっている間しか変えられないため、最後のワードの前でFIFOが空になるのを待ちます。
+//! その間データ線は1ビット分より少し長くLowになりますが、リセット時間よりは十分短く済みます。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, PixelFormat, Strip, Timing, Ws2812Direct, pio_regs};
+
+/// ピクセルを詰めて書き込むWS2812ドライバ
+///
+/// フレーム間のリセット時間(50µs以上)は呼び出し側で確保してください。
+pub struct Ws2812Packed<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+}
+
+impl<P, SM, I> Ws2812Packed<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        Self::with_timing(pin, pio, sm, clock_freq, Timing::WS2812)
+    }
+
+    /// `timing`の波形で送るように初期化します。
+    pub fn with_timing(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        timing: Timing,
+    ) -> Self {
+        let driver = Ws2812Direct::with_pull_threshold(pin, pio, sm, clock_freq, timing, 32);
+        Self { driver }
+    }
+
+    /// `format`の並びで送るように設定します。
+    pub fn set_format(&mut self, format: PixelFormat) {
+        self.driver.parts.format = format;
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> Ws2812Direct<P, SM, I> {
+        self.driver
+    }
+
+    /// オートプルの閾値を`bits`にします。ステートマシンが止まっているときに呼んでください。
+    fn set_pull_threshold(&self, bits: u32) {
+        // Safety: このステートマシンの閾値だけを書き換えます。32bitは0で表します。
+        pio_regs::<P>()
+            .sm(SM::id())
+            .sm_shiftctrl()
+            .modify(|_, w| unsafe { w.pull_thresh().bits((bits % 32) as u8) });
+    }
+
+    fn write_word(&mut self, word: u32) {
+        while !self.driver.tx.write(word) {
+            self.driver.wait();
+        }
+    }
+}
+
+impl<P, SM, I> SmartLedsWrite for Ws2812Packed<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let bits = self.driver.format().bits() as u32;
+        // まだ書き込んでいないビットを下位に溜めます。
+        let (mut acc, mut len) = (0u64, 0);
+        for item in iterator {
+            let word = Strip::encode(&self.driver, item.into());
+            acc = (acc << bits) | (word >> (32 - bits)) as u64;
+            len += bits;
+            if len >= 32 {
+                len -= 32;
+                self.write_word((acc >> len) as u32);
+            }
+        }
+        if len > 0 {
+            self.driver.wait_idle();
+            self.set_pull_threshold(len);
+            self.write_word((acc << (32 - len)) as u32);
+            self.driver.wait_idle();
+            self.set_pull_threshold(32);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! `palette`クレートの色を受け取るためのドライバです。`palette`フィーチャーで有効になります。
+//!
+//! ```ignore
+//! let mut ws = PaletteWrite::new(ws);
+//! ws.write((0..60).map(|i| Oklch::new(0.7, 0.15, i as f32 * 6.0)))?;
+//! ```
+
+use palette::{Hsl, Hsluv, Hsv, Hwb, IntoColor, LinSrgb, Okhsl, Okhsv, Oklab, Oklch, Srgb, Xyz};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+/// `palette`の色から変換した8bitのsRGBの色
+///
+/// `palette`の色は[`RGB8`]に直接変換できないので、この型を経由します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PaletteColor(pub RGB8);
+
+impl From<PaletteColor> for RGB8 {
+    fn from(color: PaletteColor) -> Self {
+        color.0
+    }
+}
+
+impl From<Srgb<u8>> for PaletteColor {
+    fn from(color: Srgb<u8>) -> Self {
+        Self(RGB8::new(color.red, color.green, color.blue))
+    }
+}
+
+impl From<Srgb<f32>> for PaletteColor {
+    fn from(color: Srgb<f32>) -> Self {
+        color.into_format::<u8>().into()
+    }
+}
+
+/// sRGBへ変換できる`palette`の色に[`PaletteColor`]への変換を実装します。
+macro_rules! impl_from_color {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl From<$ty> for PaletteColor {
+                fn from(color: $ty) -> Self {
+                    let srgb: Srgb<f32> = color.into_color();
+                    srgb.into()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_color!(
+    LinSrgb<f32>,
+    Hsl<palette::encoding::Srgb, f32>,
+    Hsv<palette::encoding::Srgb, f32>,
+    Hwb<palette::encoding::Srgb, f32>,
+    Hsluv<palette::white_point::D65, f32>,
+    Okhsl<f32>,
+    Okhsv<f32>,
+    Oklab<f32>,
+    Oklch<f32>,
+    Xyz<palette::white_point::D65, f32>,
+);
+
+/// `palette`の色を受け取り、sRGBに変換して送るドライバ
+pub struct PaletteWrite<W>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    driver: W,
+}
+
+impl<W> PaletteWrite<W>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    pub fn new(driver: W) -> Self {
+        Self { driver }
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> W {
+        self.driver
+    }
+}
+
+impl<W> SmartLedsWrite for PaletteWrite<W>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+{
+    type Color = PaletteColor;
+    type Error = W::Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), W::Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        self.driver
+            .write(iterator.into_iter().map(|item| item.into().0))
+    }
+}
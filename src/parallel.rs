@@ -0,0 +1,164 @@
+//! 1つのステートマシンで最大8本のストリップを同時に送るドライバです。
+//!
+//! `out pins`で連続したピンに各ストリップの1ビットずつを同時に出力します。
+//! FIFOに書き込むワードには全ストリップのビットを並べ替えて(ビットプレーンに転置して)詰めるので、
+//! 1ピクセルあたり6ワードになります。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{Buffers, PIOBuilder, PinDir, ShiftDirection},
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, Tx, UninitStateMachine},
+};
+use smart_leds_trait::RGB8;
+
+use crate::{Padding, Waiter, clock_divisor, grb_word};
+
+/// 複数のストリップを同時に送信するWS2812ドライバ
+///
+/// `N`本のストリップを連続した`N`本のピンに接続してください。
+/// フレーム間のリセット時間(50µs以上)は呼び出し側で確保してください。
+pub struct Ws2812Parallel<P, SM, I, const N: usize>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    tx: Tx<(P, SM)>,
+    _pins: [I; N],
+    padding: Padding,
+    waiter: Waiter,
+}
+
+impl<P, SM, I, const N: usize> Ws2812Parallel<P, SM, I, N>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// `pins`はGPIO番号が1つずつ増えるように並べてください。
+    ///
+    /// 型を揃えるために、ピンは`into_dyn_pin`などで変換しておく必要があります。
+    pub fn new(
+        pins: [I; N],
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        const T1: u8 = 2; // start bit
+        const T2: u8 = 5; // data bit
+        const T3: u8 = 3; // stop bit
+        const CYCLES_PER_BIT: u32 = (T1 + T2 + T3) as u32;
+
+        assert!((1..=8).contains(&N), "1 to 8 strips are supported.");
+
+        // PIOに入れるプログラム
+        let program = pio_proc::pio_asm!(
+            ".define public T1 2",
+            ".define public T2 5",
+            ".define public T3 3",
+            ".wrap_target",
+            "    out x, 8",
+            "    mov pins, !null    [T1 - 1]",
+            "    mov pins, x        [T2 - 1]",
+            "    mov pins, null     [T3 - 2]",
+            ".wrap",
+        );
+
+        let installed = pio.install(&program.program).unwrap();
+
+        let (int, frac) = clock_divisor(clock_freq, HertzU32::kHz(800), CYCLES_PER_BIT);
+
+        let pins = pins.map(|pin| pin.into());
+        let base = pins[0].id().num;
+        for (i, pin) in pins.iter().enumerate() {
+            assert_eq!(
+                pin.id().num,
+                base + i as u8,
+                "pins must be consecutive GPIOs"
+            );
+        }
+        let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+            .buffers(Buffers::OnlyTx)
+            .out_pins(base, N as u8)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(32)
+            .clock_divisor_fixed_point(int, frac)
+            .build(sm);
+        sm.set_pindirs(pins.iter().map(|pin| (pin.id().num, PinDir::Output)));
+        sm.start();
+
+        Self {
+            tx,
+            _pins: pins.map(I::from),
+            padding: Padding::Black,
+            waiter: Waiter::default(),
+        }
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。
+    /// [`Ws2812Direct::set_wait_hook`](crate::Ws2812Direct::set_wait_hook)を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.waiter.set_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。
+    /// [`Ws2812Direct::set_wait_for_event`](crate::Ws2812Direct::set_wait_for_event)を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.waiter.set_irq(irq);
+    }
+
+    /// 短いストリップに送るデータを設定します。
+    pub fn set_padding(&mut self, padding: Padding) {
+        self.padding = padding;
+    }
+
+    /// 全てのストリップへ同時に書き込みます。
+    ///
+    /// ストリップごとに長さが違っても構いません。
+    /// 短いストリップには、最も長いストリップが終わるまで[`Padding`]のデータを送ります。
+    pub fn write<T, C>(&mut self, strips: [T; N])
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<RGB8>,
+    {
+        let mut iters = strips.map(IntoIterator::into_iter);
+        let mut words = [0; N];
+        loop {
+            let mut any = false;
+            for (word, iter) in words.iter_mut().zip(iters.iter_mut()) {
+                match iter.next() {
+                    Some(item) => {
+                        *word = grb_word(item.into()) >> 8;
+                        any = true;
+                    }
+                    None if self.padding == Padding::Black => *word = 0,
+                    None => {}
+                }
+            }
+            if !any {
+                return;
+            }
+            for word in transpose(&words) {
+                while !self.tx.write(word) {
+                    self.waiter.wait(&self.tx);
+                }
+            }
+        }
+    }
+}
+
+/// 各ストリップの24bitのピクセルを、ビットごとに全ストリップのビットを
+/// 1バイトに並べた6ワードに転置します。
+fn transpose<const N: usize>(words: &[u32; N]) -> [u32; 6] {
+    let mut planes = [0; 6];
+    for bit in 0..24 {
+        let mut plane = 0;
+        for (strip, word) in words.iter().enumerate() {
+            plane |= ((word >> (23 - bit)) & 1) << strip;
+        }
+        planes[bit / 4] |= plane << (24 - 8 * (bit % 4));
+    }
+    planes
+}
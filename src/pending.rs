@@ -0,0 +1,82 @@
+//! ブロックせずに少しずつ書き込むための`nb`スタイルのAPIです。
+//!
+//! ```ignore
+//! let mut pending = ws.write_nb(pixels.iter().copied())?;
+//! loop {
+//!     match pending.poll() {
+//!         Ok(()) => break,
+//!         Err(nb::Error::WouldBlock) => other_work(),
+//!         Err(nb::Error::Other(e)) => return Err(e),
+//!     }
+//! }
+//! ```
+
+use rp235x_hal::{
+    gpio::AnyPin,
+    pio::{PIOExt, StateMachineIndex},
+};
+use smart_leds_trait::RGB8;
+
+use crate::{
+    Error, Ws2812Direct,
+    feed::{Feeder, Frame},
+};
+
+/// 書き込み途中のフレーム
+pub struct PendingWrite<'a, P, SM, I, T>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    T: Iterator,
+    T::Item: Into<RGB8>,
+{
+    driver: &'a mut Ws2812Direct<P, SM, I>,
+    frame: Frame<T>,
+}
+
+impl<P, SM, I> Ws2812Direct<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// ブロックせずに書き込みを始めます。
+    ///
+    /// 返り値の[`PendingWrite::poll`]を`Ok`が返るまで呼んでください。
+    /// 長さの分かるイテレーターがストリップより長い場合は、何も送らずに[`Error::LengthMismatch`]を返します。
+    pub fn write_nb<T>(
+        &mut self,
+        iterator: T,
+    ) -> Result<PendingWrite<'_, P, SM, I, T::IntoIter>, Error>
+    where
+        T: IntoIterator,
+        T::Item: Into<RGB8>,
+    {
+        let frame = self.start_frame(Feeder::new(iterator.into_iter()))?;
+        Ok(PendingWrite {
+            driver: self,
+            frame,
+        })
+    }
+}
+
+impl<P, SM, I, T> PendingWrite<'_, P, SM, I, T>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    T: Iterator,
+    T::Item: Into<RGB8>,
+{
+    /// FIFOに入るだけ書き込みます。
+    ///
+    /// まだ書き込むピクセルが残っていれば`WouldBlock`を返します。
+    /// ストリップの長さや統計は[`SmartLedsWrite::write`](smart_leds_trait::SmartLedsWrite::write)と同じように扱います。
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        match self.driver.feed_step(&mut self.frame, usize::MAX) {
+            Some(result) => result.map_err(nb::Error::Other),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+}
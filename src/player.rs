@@ -0,0 +1,114 @@
+//! フラッシュに置いたアニメーションを再生するプレイヤーです。
+//!
+//! フレームは`const`や`static`の配列から直接読み出して送るので、
+//! RAMをほとんど使わず、フレームごとの描画処理も必要ありません。
+//!
+//! ```ignore
+//! static FRAMES: [RGB8; 3 * 8] = [/* 8個のLED × 3フレーム */];
+//!
+//! let mut player = Player::new(&FRAMES, 8, 30.Hz(), timer.count_down());
+//! player.set_looping(true);
+//! while player.step(&mut ws)? {}
+//! ```
+
+use cortex_m::prelude::_embedded_hal_timer_CountDown;
+use rp235x_hal::{
+    fugit::{ExtU32, HertzU32},
+    timer::{CountDown, TimerDevice},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+/// アニメーションのプレイヤー
+pub struct Player<'a, 'timer, D>
+where
+    D: TimerDevice,
+{
+    frames: &'a [RGB8],
+    frame_len: usize,
+    /// 1フレームの時間(µs)
+    period_micros: u32,
+    cd: CountDown<'timer, D>,
+    /// 次に送るフレーム
+    index: usize,
+    looping: bool,
+    started: bool,
+}
+
+impl<'a, 'timer, D> Player<'a, 'timer, D>
+where
+    D: TimerDevice,
+{
+    /// `frames`は`frame_len`個ずつのピクセルを並べたものです。
+    /// 最後の`frame_len`個に満たない部分は無視します。
+    pub fn new(
+        frames: &'a [RGB8],
+        frame_len: usize,
+        frame_rate: HertzU32,
+        cd: CountDown<'timer, D>,
+    ) -> Self {
+        assert!(frame_len > 0, "frame_len must not be 0.");
+        assert!(frame_rate.to_Hz() > 0, "frame_rate must not be 0.");
+        Self {
+            frames,
+            frame_len,
+            period_micros: 1_000_000 / frame_rate.to_Hz(),
+            cd,
+            index: 0,
+            looping: false,
+            started: false,
+        }
+    }
+
+    /// カウントダウンを返します。
+    pub fn free(self) -> CountDown<'timer, D> {
+        self.cd
+    }
+
+    /// 最後のフレームの後に最初のフレームへ戻るかを設定します。
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// フレームの数を返します。
+    pub fn frame_count(&self) -> usize {
+        self.frames.len() / self.frame_len
+    }
+
+    /// 次に送るフレームの番号を返します。
+    pub fn position(&self) -> usize {
+        self.index
+    }
+
+    /// 最初のフレームから再生し直します。
+    pub fn rewind(&mut self) {
+        self.index = 0;
+        self.started = false;
+    }
+
+    /// 次のフレームの時刻まで待って送ります。
+    ///
+    /// 最後まで再生し終えていた場合は何も送らずに`false`を返します。
+    pub fn step<W>(&mut self, driver: &mut W) -> Result<bool, W::Error>
+    where
+        W: SmartLedsWrite,
+        RGB8: Into<W::Color>,
+    {
+        if self.index >= self.frame_count() {
+            if !self.looping || self.frame_count() == 0 {
+                return Ok(false);
+            }
+            self.index = 0;
+        }
+        if self.started {
+            let _ = nb::block!(self.cd.wait());
+        }
+        self.cd.start(self.period_micros.micros());
+        self.started = true;
+
+        let start = self.index * self.frame_len;
+        let frame = &self.frames[start..start + self.frame_len];
+        self.index += 1;
+        driver.write(frame.iter().copied())?;
+        Ok(true)
+    }
+}
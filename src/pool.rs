@@ -0,0 +1,232 @@
+//! PIO0/PIO1のステートマシンを空いているものから順に割り当てるプールです。
+//!
+//! たくさんのストリップを使うアプリケーションでも、どのステートマシンが
+//! 空いているかを管理したり、ドライバの型を書き分けたりする必要がなくなります。
+//! プログラムは[`SharedProgram`]でPIOブロックごとに1つだけインストールされます。
+//!
+//! ```ignore
+//! let mut pool = Ws2812Pool::new(
+//!     pac.PIO0.split(&mut pac.RESETS),
+//!     pac.PIO1.split(&mut pac.RESETS),
+//!     clock_freq,
+//! );
+//! let mut strip = pool.allocate(pins.gpio2.into_dyn_pin()).ok().unwrap();
+//! strip.write(pixels.iter().copied()).unwrap();
+//! ```
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::{DynPinId, Function, FunctionNull, FunctionPio0, FunctionPio1, Pin, PullType},
+    pac::{PIO0, PIO1},
+    pio::{PIO, SM0, SM1, SM2, SM3, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, SharedProgram, Strip, Ws2812Direct};
+
+/// `PIOExt::split`で得られるPIOブロックとステートマシン
+pub type Split<P> = (
+    PIO<P>,
+    UninitStateMachine<(P, SM0)>,
+    UninitStateMachine<(P, SM1)>,
+    UninitStateMachine<(P, SM2)>,
+    UninitStateMachine<(P, SM3)>,
+);
+
+/// プールから割り当てられたドライバ
+pub enum PooledStrip<M: PullType> {
+    Pio0Sm0(Ws2812Direct<PIO0, SM0, Pin<DynPinId, FunctionPio0, M>>),
+    Pio0Sm1(Ws2812Direct<PIO0, SM1, Pin<DynPinId, FunctionPio0, M>>),
+    Pio0Sm2(Ws2812Direct<PIO0, SM2, Pin<DynPinId, FunctionPio0, M>>),
+    Pio0Sm3(Ws2812Direct<PIO0, SM3, Pin<DynPinId, FunctionPio0, M>>),
+    Pio1Sm0(Ws2812Direct<PIO1, SM0, Pin<DynPinId, FunctionPio1, M>>),
+    Pio1Sm1(Ws2812Direct<PIO1, SM1, Pin<DynPinId, FunctionPio1, M>>),
+    Pio1Sm2(Ws2812Direct<PIO1, SM2, Pin<DynPinId, FunctionPio1, M>>),
+    Pio1Sm3(Ws2812Direct<PIO1, SM3, Pin<DynPinId, FunctionPio1, M>>),
+}
+
+/// 全ての種類のドライバに同じ処理をします。
+macro_rules! dispatch {
+    ($strip:expr, $driver:ident => $body:expr) => {
+        match $strip {
+            PooledStrip::Pio0Sm0($driver) => $body,
+            PooledStrip::Pio0Sm1($driver) => $body,
+            PooledStrip::Pio0Sm2($driver) => $body,
+            PooledStrip::Pio0Sm3($driver) => $body,
+            PooledStrip::Pio1Sm0($driver) => $body,
+            PooledStrip::Pio1Sm1($driver) => $body,
+            PooledStrip::Pio1Sm2($driver) => $body,
+            PooledStrip::Pio1Sm3($driver) => $body,
+        }
+    };
+}
+
+impl<M: PullType> Strip for PooledStrip<M> {
+    fn write_word(&mut self, word: u32) -> bool {
+        dispatch!(self, driver => driver.write_word(word))
+    }
+
+    fn wait_drained(&self) {
+        dispatch!(self, driver => Strip::wait_drained(driver))
+    }
+
+    fn wait(&self) {
+        dispatch!(self, driver => Strip::wait(driver))
+    }
+
+    fn encode(&self, color: RGB8) -> u32 {
+        dispatch!(self, driver => driver.encode(color))
+    }
+}
+
+impl<M: PullType> SmartLedsWrite for PooledStrip<M> {
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        dispatch!(self, driver => driver.write(iterator))
+    }
+}
+
+/// ステートマシンを割り当てるプール
+pub struct Ws2812Pool {
+    clock_freq: HertzU32,
+    pio0: PIO<PIO0>,
+    pio1: PIO<PIO1>,
+    program0: SharedProgram<PIO0>,
+    program1: SharedProgram<PIO1>,
+    pio0_sm0: Option<UninitStateMachine<(PIO0, SM0)>>,
+    pio0_sm1: Option<UninitStateMachine<(PIO0, SM1)>>,
+    pio0_sm2: Option<UninitStateMachine<(PIO0, SM2)>>,
+    pio0_sm3: Option<UninitStateMachine<(PIO0, SM3)>>,
+    pio1_sm0: Option<UninitStateMachine<(PIO1, SM0)>>,
+    pio1_sm1: Option<UninitStateMachine<(PIO1, SM1)>>,
+    pio1_sm2: Option<UninitStateMachine<(PIO1, SM2)>>,
+    pio1_sm3: Option<UninitStateMachine<(PIO1, SM3)>>,
+}
+
+/// 空いているステートマシンを探してドライバを作ります。
+macro_rules! allocate {
+    ($self:ident, $pin:ident, $($sm:ident, $pio:ident, $program:ident, $function:ty, $variant:ident;)*) => {
+        $(
+            if $self.$sm.is_some() {
+                let pin = match $pin.try_into_function::<$function>() {
+                    Ok(pin) => pin,
+                    Err(pin) => return Err(pin),
+                };
+                let sm = $self.$sm.take().unwrap();
+                let driver = Ws2812Direct::new_shared(
+                    pin,
+                    &mut $self.$pio,
+                    sm,
+                    $self.clock_freq,
+                    &mut $self.$program,
+                );
+                return Ok(PooledStrip::$variant(driver));
+            }
+        )*
+    };
+}
+
+/// ドライバを解放してステートマシンをプールに戻します。
+macro_rules! release {
+    ($self:ident, $strip:ident, $($sm:ident, $pio:ident, $program:ident, $variant:ident;)*) => {
+        match $strip {
+            $(
+                PooledStrip::$variant(driver) => {
+                    let (pin, sm) = driver.free_shared(&mut $self.$pio, &mut $self.$program);
+                    $self.$sm = Some(sm);
+                    into_null(pin)
+                }
+            )*
+        }
+    };
+}
+
+fn into_null<F: Function, M: PullType>(pin: Pin<DynPinId, F, M>) -> Pin<DynPinId, FunctionNull, M> {
+    match pin.try_into_function() {
+        Ok(pin) => pin,
+        Err(_) => unreachable!("every pin supports FunctionNull"),
+    }
+}
+
+impl Ws2812Pool {
+    pub fn new(pio0: Split<PIO0>, pio1: Split<PIO1>, clock_freq: HertzU32) -> Self {
+        Self {
+            clock_freq,
+            pio0: pio0.0,
+            pio1: pio1.0,
+            program0: SharedProgram::new(),
+            program1: SharedProgram::new(),
+            pio0_sm0: Some(pio0.1),
+            pio0_sm1: Some(pio0.2),
+            pio0_sm2: Some(pio0.3),
+            pio0_sm3: Some(pio0.4),
+            pio1_sm0: Some(pio1.1),
+            pio1_sm1: Some(pio1.2),
+            pio1_sm2: Some(pio1.3),
+            pio1_sm3: Some(pio1.4),
+        }
+    }
+
+    /// 空いているステートマシンの数を返します。
+    pub fn available(&self) -> usize {
+        [
+            self.pio0_sm0.is_some(),
+            self.pio0_sm1.is_some(),
+            self.pio0_sm2.is_some(),
+            self.pio0_sm3.is_some(),
+            self.pio1_sm0.is_some(),
+            self.pio1_sm1.is_some(),
+            self.pio1_sm2.is_some(),
+            self.pio1_sm3.is_some(),
+        ]
+        .iter()
+        .filter(|&&free| free)
+        .count()
+    }
+
+    /// 空いているステートマシンで`pin`を駆動するドライバを作ります。
+    ///
+    /// 空いているステートマシンがない場合は`pin`をそのまま返します。
+    pub fn allocate<F, M>(
+        &mut self,
+        pin: Pin<DynPinId, F, M>,
+    ) -> Result<PooledStrip<M>, Pin<DynPinId, F, M>>
+    where
+        F: Function,
+        M: PullType,
+    {
+        allocate!(self, pin,
+            pio0_sm0, pio0, program0, FunctionPio0, Pio0Sm0;
+            pio0_sm1, pio0, program0, FunctionPio0, Pio0Sm1;
+            pio0_sm2, pio0, program0, FunctionPio0, Pio0Sm2;
+            pio0_sm3, pio0, program0, FunctionPio0, Pio0Sm3;
+            pio1_sm0, pio1, program1, FunctionPio1, Pio1Sm0;
+            pio1_sm1, pio1, program1, FunctionPio1, Pio1Sm1;
+            pio1_sm2, pio1, program1, FunctionPio1, Pio1Sm2;
+            pio1_sm3, pio1, program1, FunctionPio1, Pio1Sm3;
+        );
+        Err(pin)
+    }
+
+    /// ドライバを解放してステートマシンをプールに戻し、ピンを返します。
+    pub fn release<M: PullType>(
+        &mut self,
+        strip: PooledStrip<M>,
+    ) -> Pin<DynPinId, FunctionNull, M> {
+        release!(self, strip,
+            pio0_sm0, pio0, program0, Pio0Sm0;
+            pio0_sm1, pio0, program0, Pio0Sm1;
+            pio0_sm2, pio0, program0, Pio0Sm2;
+            pio0_sm3, pio0, program0, Pio0Sm3;
+            pio1_sm0, pio1, program1, Pio1Sm0;
+            pio1_sm1, pio1, program1, Pio1Sm1;
+            pio1_sm2, pio1, program1, Pio1Sm2;
+            pio1_sm3, pio1, program1, Pio1Sm3;
+        )
+    }
+}
@@ -0,0 +1,76 @@
+//! 1つのPIOブロックの中でWS2812のプログラムを共有する仕組みです。
+//!
+//! PIOの命令メモリは32命令しかないので、ドライバごとにプログラムを
+//! インストールするとすぐに足りなくなります。
+//! [`SharedProgram`]を通して作ったドライバは、PIOブロックごとに1つだけ
+//! インストールしたプログラムを共有し、最後のドライバが解放されたときにアンインストールします。
+//!
+//! ```ignore
+//! let mut program = SharedProgram::new();
+//! let a = Ws2812Direct::new_shared(pin_a, &mut pio, sm0, clock_freq, &mut program);
+//! let b = Ws2812Direct::new_shared(pin_b, &mut pio, sm1, clock_freq, &mut program);
+//! ```
+
+use rp235x_hal::pio::{InstalledProgram, PIO, PIOExt};
+
+use crate::{Timing, install};
+
+/// ドライバが使うPIOのプログラムの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgramVariant {
+    /// 4命令のプログラム
+    #[default]
+    Standard,
+    /// 3命令のプログラム
+    ///
+    /// 他の周辺機器とPIOの命令メモリを分け合う場合に使えます。
+    /// サイドセットを省略できるようにする分ディレイが短くなるので、
+    /// T1、T2、T3はそれぞれ8サイクルまでにしてください。
+    Minimal,
+}
+
+/// 共有しているWS2812のプログラム
+pub struct SharedProgram<P: PIOExt> {
+    installed: Option<InstalledProgram<P>>,
+    users: usize,
+}
+
+impl<P: PIOExt> Default for SharedProgram<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: PIOExt> SharedProgram<P> {
+    pub const fn new() -> Self {
+        Self {
+            installed: None,
+            users: 0,
+        }
+    }
+
+    /// プログラムを使っているドライバの数を返します。
+    pub fn users(&self) -> usize {
+        self.users
+    }
+
+    /// 必要ならプログラムをインストールし、その複製を返します。
+    pub(crate) fn acquire(&mut self, pio: &mut PIO<P>) -> InstalledProgram<P> {
+        let installed = self
+            .installed
+            .get_or_insert_with(|| install(pio, Timing::WS2812));
+        self.users += 1;
+        // Safety: 複製を使うドライバを数え、全て返されるまでアンインストールしません。
+        unsafe { installed.share() }
+    }
+
+    /// 複製を返し、使うドライバがなくなったらアンインストールします。
+    pub(crate) fn release(&mut self, pio: &mut PIO<P>, _installed: InstalledProgram<P>) {
+        self.users -= 1;
+        if self.users == 0
+            && let Some(installed) = self.installed.take()
+        {
+            pio.uninstall(installed);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! 割り込みでフレームを順に送るキューです。
+//!
+//! 書き込む側は数フレーム先まで[`Ws2812Queue::enqueue`]で積んでおけるので、
+//! 自分の処理の時間が多少ばらついても表示は一定の間隔で更新されます。
+//! FIFOの補充はPIOの"TX not full"割り込みで、フレーム間のリセット時間は
+//! タイマーのアラームで待つので、両方の割り込みハンドラから[`Ws2812Queue::on_interrupt`]を呼んでください。
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn PIO0_IRQ_0() {
+//!     critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt().ok());
+//! }
+//!
+//! #[interrupt]
+//! fn TIMER0_IRQ_0() {
+//!     critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt().ok());
+//! }
+//! ```
+
+use rp235x_hal::{
+    fugit::{ExtU32, HertzU32},
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, UninitStateMachine},
+    timer::{Alarm, ScheduleAlarmError},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, Ws2812Direct, dma::DRAIN_WORDS};
+
+/// 最大`N`ピクセルのフレームを`Q`個まで積めるキュー
+pub struct Ws2812Queue<P, SM, I, A, const N: usize, const Q: usize>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    A: Alarm,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    irq: PioIRQ,
+    alarm: A,
+    frames: [[u32; N]; Q],
+    lens: [usize; Q],
+    /// 先頭のフレームの位置
+    head: usize,
+    /// 積まれているフレームの数
+    count: usize,
+    /// 先頭のフレームの次に書き込むワード
+    cursor: usize,
+    /// リセット時間を待っている
+    latching: bool,
+    /// アラームを予約できず、次に呼ばれたときに予約し直すリセット時間(µs)
+    retry_latch: Option<u32>,
+}
+
+impl<P, SM, I, A, const N: usize, const Q: usize> Ws2812Queue<P, SM, I, A, N, Q>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    A: Alarm,
+{
+    /// `irq`はFIFOの補充に、`alarm`はリセット時間の待機に使います。
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        irq: PioIRQ,
+        alarm: A,
+    ) -> Self {
+        let driver = Ws2812Direct::new(pin, pio, sm, clock_freq);
+        Self {
+            driver,
+            irq,
+            alarm,
+            frames: [[0; N]; Q],
+            lens: [0; Q],
+            head: 0,
+            count: 0,
+            cursor: 0,
+            latching: false,
+            retry_latch: None,
+        }
+    }
+
+    /// 積まれているフレームの数を返します。送っている途中のフレームも含みます。
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// キューが空かを返します。
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// キューが一杯かを返します。
+    pub fn is_full(&self) -> bool {
+        self.count == Q
+    }
+
+    /// フレームを積みます。
+    ///
+    /// キューが一杯、またはピクセルが`N`個より多い場合は積まずに`false`を返します。
+    pub fn enqueue<T, C>(&mut self, frame: T) -> bool
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<RGB8>,
+    {
+        let frame = frame.into_iter();
+        if self.is_full() || frame.size_hint().0 > N {
+            return false;
+        }
+        let slot = (self.head + self.count) % Q;
+        let format = self.driver.format();
+        let mut len = 0;
+        for item in frame {
+            let Some(word) = self.frames[slot].get_mut(len) else {
+                return false;
+            };
+            *word = format.encode(item.into());
+            len += 1;
+        }
+        self.lens[slot] = len;
+        self.count += 1;
+        // アラームを予約し直す必要がある場合も、割り込みから`on_interrupt`でやり直します。
+        if !self.latching {
+            self.driver.tx.enable_tx_not_full_interrupt(self.irq);
+        }
+        true
+    }
+
+    /// 送っている途中のフレームを捨てます。
+    ///
+    /// キューに残っているフレームは、リセット時間が経ってから続けて送ります。
+    /// アラームを予約できなかった場合は`Err`を返し、次の[`Ws2812Queue::on_interrupt`]で予約し直します。
+    pub fn abort(&mut self) -> Result<(), ScheduleAlarmError> {
+        self.driver.tx.disable_tx_not_full_interrupt(self.irq);
+        if self.cursor > 0 {
+            self.head = (self.head + 1) % Q;
+            self.count -= 1;
+            self.cursor = 0;
+        }
+        self.driver.abort();
+        self.latching = false;
+        self.start_latch(self.driver.reset_micros())
+    }
+
+    /// PIOとアラームの割り込みハンドラから呼んでください。
+    ///
+    /// リセット時間を待つアラームを予約できなかった場合は`Err`を返します。
+    /// 次のフレームは送らずに、次に呼ばれたとき(割り込みのほか、[`Ws2812Queue::enqueue`]の後)に予約し直します。
+    pub fn on_interrupt(&mut self) -> Result<(), ScheduleAlarmError> {
+        if let Some(micros) = self.retry_latch {
+            return self.start_latch(micros);
+        }
+        if self.latching {
+            if !self.alarm.finished() {
+                return Ok(());
+            }
+            self.alarm.clear_interrupt();
+            self.alarm.disable_interrupt();
+            self.latching = false;
+        }
+        if self.is_empty() {
+            self.driver.tx.disable_tx_not_full_interrupt(self.irq);
+            return Ok(());
+        }
+
+        let (frame, len) = (&self.frames[self.head], self.lens[self.head]);
+        while self.cursor < len && self.driver.tx.write(frame[self.cursor]) {
+            self.cursor += 1;
+        }
+        if self.cursor < len {
+            self.driver.tx.enable_tx_not_full_interrupt(self.irq);
+            return Ok(());
+        }
+
+        // フレームを全てFIFOに書き込んだので、送り終えてラッチされるまで次のフレームを待たせます。
+        self.head = (self.head + 1) % Q;
+        self.count -= 1;
+        self.cursor = 0;
+        let micros = DRAIN_WORDS * self.driver.word_micros() + self.driver.reset_micros();
+        self.start_latch(micros)
+    }
+
+    /// `micros`のリセット時間が経ったらアラームの割り込みで起こされるようにします。
+    ///
+    /// 予約できなかった場合は、"TX not full"割り込みを止めたまま次に呼ばれるのを待ちます。
+    fn start_latch(&mut self, micros: u32) -> Result<(), ScheduleAlarmError> {
+        self.driver.tx.disable_tx_not_full_interrupt(self.irq);
+        self.alarm.clear_interrupt();
+        match self.alarm.schedule(micros.micros()) {
+            Ok(()) => {
+                self.alarm.enable_interrupt();
+                self.latching = true;
+                self.retry_latch = None;
+                Ok(())
+            }
+            Err(error) => {
+                self.retry_latch = Some(micros);
+                Err(error)
+            }
+        }
+    }
+}
+
+impl<P, SM, I, A, const N: usize, const Q: usize> SmartLedsWrite for Ws2812Queue<P, SM, I, A, N, Q>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    A: Alarm,
+{
+    type Color = RGB8;
+    type Error = Error;
+    /// フレームを積みます。
+    ///
+    /// キューが一杯の場合は[`Error::Busy`]を、
+    /// ピクセルが`N`個より多い場合は[`Error::LengthMismatch`]を返します。
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        if self.is_full() {
+            Err(Error::Busy)
+        } else if self.enqueue(iterator) {
+            Ok(())
+        } else {
+            Err(Error::LengthMismatch)
+        }
+    }
+}
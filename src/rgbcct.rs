@@ -0,0 +1,121 @@
+//! WS2805などの白を2色持つ5色のLEDのドライバです。
+//!
+//! 1ピクセルは40bit(RGB、寒色の白、暖色の白)なので、
+//! 1回のオートプルで8bitずつ取り出し、1バイトを1ワードの上位8bitとして書き込みます。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGBCCT, SmartLedsWrite};
+
+use crate::{
+    Error, Stats, Strip, Timing, Ws2812Direct,
+    feed::{Encoding, Feeder, Words},
+    ops::scale_channel,
+};
+
+/// 色を1バイト1ワードの5ワードに変換します。
+#[derive(Clone, Copy)]
+struct Rgbcw {
+    /// 全体の明るさ
+    fraction: u8,
+}
+
+impl Encoding for Rgbcw {
+    type Color = RGBCCT<u8>;
+
+    fn encode<S>(&self, _strip: &S, color: RGBCCT<u8>) -> Words
+    where
+        S: Strip + ?Sized,
+    {
+        let bytes = [color.r, color.g, color.b, color.a.cold, color.a.warm];
+        Words::new(&bytes.map(|byte| (scale_channel(byte, self.fraction) as u32) << 24))
+    }
+}
+
+/// WS2805 (RGBCW)のドライバ
+///
+/// 色は[`RGBCCT<u8>`]で渡します。
+/// フレーム間のリセット時間(280µs以上)は呼び出し側で確保してください。
+pub struct Ws2805<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+}
+
+impl<P, SM, I> Ws2805<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        let driver = Ws2812Direct::with_pull_threshold(pin, pio, sm, clock_freq, Timing::WS2812, 8);
+        Self { driver }
+    }
+
+    /// 全体の明るさを設定します。[`Ws2812Direct::set_brightness`]を参照してください。
+    ///
+    /// 2色の白を含む全てのチャンネルを同じ割合で暗くします。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.driver.set_brightness(brightness);
+    }
+
+    /// ストリップのLEDの数を設定します。[`Ws2812Direct::set_length`]を参照してください。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.driver.set_length(length);
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。[`Ws2812Direct::set_wait_hook`]を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.driver.set_wait_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。[`Ws2812Direct::set_wait_for_event`]を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.driver.set_wait_for_event(irq);
+    }
+
+    /// 送ったフレームなどの数を返します。[`Ws2812Direct::stats`]を参照してください。
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> Ws2812Direct<P, SM, I> {
+        self.driver
+    }
+}
+
+impl<P, SM, I> SmartLedsWrite for Ws2805<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGBCCT<u8>;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let encoding = Rgbcw {
+            fraction: self.driver.fraction(),
+        };
+        let frame = self
+            .driver
+            .start_frame(Feeder::with_encoding(iterator.into_iter(), encoding))?;
+        self.driver.feed_frame(frame)
+    }
+}
@@ -0,0 +1,149 @@
+//! SK6812 RGBWなどの4色のLEDのドライバです。
+//!
+//! 1ピクセルは32bit(GRBW)で、1回のオートプルで32bitずつ取り出します。
+//! 明るさやストリップの長さ、統計はRGBのドライバと同じ処理を通ります。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, RGBW, SmartLedsWrite, White};
+
+use crate::{
+    Error, PixelFormat, Stats, Ws2812Direct,
+    feed::{Feeder, Rgbw},
+};
+
+/// RGBの色から白のチャンネルを求める方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhiteMode {
+    /// 白は使わない
+    #[default]
+    Off,
+    /// 3色の最小値を白にして、3色から引く
+    ///
+    /// 色味を保ったまま、混色の白の代わりに白のLEDを使います。
+    Subtract,
+    /// 3色の最小値を白にして、3色はそのまま残す
+    ///
+    /// 色味は薄くなりますが、明るくなります。
+    Add,
+}
+
+/// `mode`の方法でRGBの色から白のチャンネルを求めます。
+pub fn extract_white(color: RGB8, mode: WhiteMode) -> RGBW<u8> {
+    let white = color.r.min(color.g).min(color.b);
+    match mode {
+        WhiteMode::Off => RGBW::new_alpha(color.r, color.g, color.b, White(0)),
+        WhiteMode::Subtract => RGBW::new_alpha(
+            color.r - white,
+            color.g - white,
+            color.b - white,
+            White(white),
+        ),
+        WhiteMode::Add => RGBW::new_alpha(color.r, color.g, color.b, White(white)),
+    }
+}
+
+/// SK6812 RGBWのドライバ
+///
+/// フレーム間のリセット時間(80µs以上)は呼び出し側で確保してください。
+pub struct Sk6812Rgbw<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    white_mode: WhiteMode,
+}
+
+impl<P, SM, I> Sk6812Rgbw<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        let driver = Ws2812Direct::with_format(pin, pio, sm, clock_freq, PixelFormat::Grbw);
+        Self {
+            driver,
+            white_mode: WhiteMode::Off,
+        }
+    }
+
+    /// [`write_rgb`](Self::write_rgb)で白のチャンネルを求める方法を設定します。
+    pub fn set_white_mode(&mut self, mode: WhiteMode) {
+        self.white_mode = mode;
+    }
+
+    /// RGBの色を、設定した方法で白のチャンネルを求めて書き込みます。
+    ///
+    /// RGBのLED向けに書いたエフェクトのコードをそのまま使えます。
+    pub fn write_rgb<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<RGB8>,
+    {
+        let mode = self.white_mode;
+        SmartLedsWrite::write(
+            self,
+            iterator
+                .into_iter()
+                .map(|item| extract_white(item.into(), mode)),
+        )
+    }
+
+    /// 全体の明るさを設定します。[`Ws2812Direct::set_brightness`]を参照してください。
+    ///
+    /// 白のチャンネルも同じ割合で暗くします。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.driver.set_brightness(brightness);
+    }
+
+    /// ストリップのLEDの数を設定します。[`Ws2812Direct::set_length`]を参照してください。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.driver.set_length(length);
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。[`Ws2812Direct::set_wait_hook`]を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.driver.set_wait_hook(hook);
+    }
+
+    /// 送ったフレームなどの数を返します。[`Ws2812Direct::stats`]を参照してください。
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> Ws2812Direct<P, SM, I> {
+        self.driver
+    }
+}
+
+impl<P, SM, I> SmartLedsWrite for Sk6812Rgbw<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGBW<u8>;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let frame = self
+            .driver
+            .start_frame(Feeder::with_encoding(iterator.into_iter(), Rgbw))?;
+        self.driver.feed_frame(frame)
+    }
+}
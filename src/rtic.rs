@@ -0,0 +1,74 @@
+//! リセット時間の待機にRTICの`Monotonic`を使うドライバです。`rtic`フィーチャーで有効になります。
+//!
+//! `CountDown`を借用しないのでライフタイムを持たず、RTICの共有リソースにそのまま置けます。
+
+use core::marker::PhantomData;
+
+use rp235x_hal::{
+    fugit::{ExtU32, HertzU32, MicrosDurationU32},
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, StateMachineIndex, UninitStateMachine},
+};
+use rtic_time::Monotonic;
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, Ws2812Direct};
+
+/// リセット時間を`M`で待つWS2812ドライバ
+pub struct Ws2812Mono<P, SM, I, M>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    M: Monotonic,
+    M::Duration: From<MicrosDurationU32>,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    _mono: PhantomData<fn() -> M>,
+}
+
+impl<P, SM, I, M> Ws2812Mono<P, SM, I, M>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    M: Monotonic,
+    M::Duration: From<MicrosDurationU32>,
+{
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        let driver = Ws2812Direct::new(pin, pio, sm, clock_freq);
+        Self {
+            driver,
+            _mono: PhantomData,
+        }
+    }
+}
+
+impl<P, SM, I, M> SmartLedsWrite for Ws2812Mono<P, SM, I, M>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    M: Monotonic,
+    M::Duration: From<MicrosDurationU32>,
+{
+    type Color = RGB8;
+    type Error = Error;
+    fn write<T, J>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = J>,
+        J: Into<Self::Color>,
+    {
+        self.driver.wait_drained();
+
+        let end = M::now() + self.driver.reset_micros().micros().into();
+        while M::now() < end {}
+
+        SmartLedsWrite::write(&mut self.driver, iterator)
+    }
+}
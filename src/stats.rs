@@ -0,0 +1,98 @@
+//! ドライバの動作を確かめるための統計です。
+
+use rp235x_hal::{
+    fugit::{ExtU32, MicrosDurationU32},
+    timer::Instant,
+};
+
+/// ドライバが送ったフレームなどの数
+///
+/// 長く動かし続ける設置で、ドライバの状態を遠くから確かめるのに使えます。
+/// どの数も`u32`の最大値を超えると0に戻ります。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// 送ったフレームの数
+    pub frames: u32,
+    /// 送ったピクセルの数
+    pub pixels: u32,
+    /// フレームの途中でステートマシンがFIFOを待って止まっているのを見つけた回数
+    pub stalls: u32,
+    /// 途中でFIFOが空になったフレームの数
+    pub underruns: u32,
+    /// FIFOが空になったフレームを送り直した回数
+    pub retries: u32,
+}
+
+/// フレームを書き込んだ間隔の最小、最大、平均
+///
+/// 本当に60FPSで更新できているかや、他の割り込みによるばらつきを調べるのに使えます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IntervalStats {
+    /// 最後にフレームを書き込んだ時刻
+    last: Option<Instant>,
+    /// 測った間隔の数
+    count: u32,
+    min: u32,
+    max: u32,
+    /// 測った間隔の合計(µs)
+    total: u64,
+}
+
+impl IntervalStats {
+    pub const fn new() -> Self {
+        Self {
+            last: None,
+            count: 0,
+            min: 0,
+            max: 0,
+            total: 0,
+        }
+    }
+
+    /// `now`にフレームを書き込んだことを記録します。
+    pub fn record(&mut self, now: Instant) {
+        if let Some(interval) = self.last.and_then(|last| now.checked_duration_since(last)) {
+            let interval = interval.to_micros().min(u32::MAX as u64) as u32;
+            if self.count == 0 {
+                self.min = interval;
+                self.max = interval;
+            } else {
+                self.min = self.min.min(interval);
+                self.max = self.max.max(interval);
+            }
+            self.count = self.count.saturating_add(1);
+            self.total = self.total.saturating_add(interval as u64);
+        }
+        self.last = Some(now);
+    }
+
+    /// 記録をすべて消します。
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 測った間隔の数を返します。
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// 最も短かった間隔を返します。
+    pub fn min(&self) -> Option<MicrosDurationU32> {
+        (self.count > 0).then(|| self.min.micros())
+    }
+
+    /// 最も長かった間隔を返します。
+    pub fn max(&self) -> Option<MicrosDurationU32> {
+        (self.count > 0).then(|| self.max.micros())
+    }
+
+    /// 間隔の平均を返します。
+    pub fn mean(&self) -> Option<MicrosDurationU32> {
+        (self.count > 0).then(|| ((self.total / self.count as u64) as u32).micros())
+    }
+
+    /// 最大と最小の差(ジッター)を返します。
+    pub fn jitter(&self) -> Option<MicrosDurationU32> {
+        (self.count > 0).then(|| (self.max - self.min).micros())
+    }
+}
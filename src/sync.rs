@@ -0,0 +1,62 @@
+//! 別々に作ったドライバのステートマシンを同時に動かし直す仕組みです。
+//!
+//! 複数のストリップで1つの面を作る場合、ステートマシンのクロック分周器の位相が
+//! ずれていると、ストリップごとにビットのタイミングが少しずつずれます。
+//! ドライバを全て作った後に[`restart_in_sync`]を呼ぶと、指定したステートマシンを
+//! 一度止め、クロック分周器を揃えてから同時に動かし直します。
+//!
+//! ```ignore
+//! let a = Ws2812Direct::new(pin_a, &mut pio0, sm0, clock_freq);
+//! let b = Ws2812Direct::new(pin_b, &mut pio0, sm1, clock_freq);
+//! let c = Ws2812Direct::new(pin_c, &mut pio1, sm0_1, clock_freq);
+//! sync::restart_in_sync(&mut pio0, 0b0011, 0b0001);
+//! ```
+
+use rp235x_hal::pio::{PIO, PIOExt};
+
+use crate::pio_regs;
+
+/// `mask`のステートマシンを止め、クロック分周器を揃えてから同時に動かします。
+///
+/// `mask`と`other_mask`のビットiがステートマシンiに対応し、
+/// `other_mask`はもう一方のPIOブロックのステートマシンを指定します。
+/// フレームを送っている途中に呼ぶと、そのフレームは崩れるので、
+/// 全てのドライバが送信を終えているときに呼んでください。
+pub fn restart_in_sync<P: PIOExt>(_pio: &mut PIO<P>, mask: u8, other_mask: u8) {
+    let (mask, other_mask) = (mask as u32 & 0xf, other_mask as u32 & 0xf);
+    let other = other_pio_mask::<P>(other_mask);
+    let ctrl = pio_regs::<P>().ctrl().as_ptr() as usize;
+    // Safety: アトミックなクリア用のエイリアスなので、他のステートマシンのビットには影響しません。
+    unsafe { ((ctrl + 0x3000) as *mut u32).write_volatile(mask << SM_ENABLE) };
+    // Safety: アトミックなセット用のエイリアスで、もう一方のPIOブロックへの指示だけを書き込みます。
+    unsafe { ((ctrl + 0x2000) as *mut u32).write_volatile(other | 1 << NEXTPREV_SM_DISABLE) };
+    // Safety: 同上で、両方のPIOブロックのステートマシンを1回の書き込みで動かします。
+    unsafe {
+        ((ctrl + 0x2000) as *mut u32).write_volatile(
+            mask << SM_ENABLE
+                | mask << CLKDIV_RESTART
+                | other
+                | 1 << NEXTPREV_SM_ENABLE
+                | 1 << NEXTPREV_CLKDIV_RESTART,
+        )
+    };
+}
+
+/// CTRLの各フィールドの位置
+const SM_ENABLE: u32 = 0;
+const CLKDIV_RESTART: u32 = 8;
+const PREV_PIO_MASK: u32 = 16;
+const NEXT_PIO_MASK: u32 = 20;
+const NEXTPREV_SM_ENABLE: u32 = 24;
+const NEXTPREV_SM_DISABLE: u32 = 25;
+const NEXTPREV_CLKDIV_RESTART: u32 = 26;
+
+/// もう一方のPIOブロックのステートマシンを選ぶビットを返します。
+///
+/// PIO0から見てPIO1は次の、PIO1から見てPIO0は前のPIOブロックです。
+fn other_pio_mask<P: PIOExt>(mask: u32) -> u32 {
+    match P::id() {
+        0 => mask << NEXT_PIO_MASK,
+        _ => mask << PREV_PIO_MASK,
+    }
+}
@@ -0,0 +1,93 @@
+//! 1ビットの波形のタイミングです。
+
+use rp235x_hal::fugit::HertzU32;
+
+/// 1ビットの波形のタイミング
+///
+/// 1ビットは`t1 + t2 + t3`サイクルで、`bit_rate`で送ります。
+/// 0のときは`t1`サイクル、1のときは`t1 + t2`サイクルだけHighになります。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    t1: u8,
+    t2: u8,
+    t3: u8,
+    bit_rate: HertzU32,
+}
+
+impl Timing {
+    /// WS2812 (800kHz)
+    pub const WS2812: Timing = Timing {
+        t1: 2,
+        t2: 5,
+        t3: 3,
+        bit_rate: HertzU32::kHz(800),
+    };
+
+    /// WS2811の低速モード (400kHz)
+    ///
+    /// 0は0.5µs、1は1.25µsだけHighになります。
+    pub const WS2811_400KHZ: Timing = Timing {
+        t1: 2,
+        t2: 3,
+        t3: 5,
+        bit_rate: HertzU32::kHz(400),
+    };
+
+    /// SK6812、SK6805 (800kHz)
+    ///
+    /// 0は0.375µs、1は0.75µsだけHighになります。
+    pub const SK6812: Timing = Timing {
+        t1: 3,
+        t2: 3,
+        t3: 4,
+        bit_rate: HertzU32::kHz(800),
+    };
+
+    /// APA106 (約580kHz)
+    ///
+    /// 0は0.34µs、1は1.38µsだけHighになります。
+    pub const APA106: Timing = Timing {
+        t1: 2,
+        t2: 6,
+        t3: 2,
+        bit_rate: HertzU32::kHz(580),
+    };
+
+    /// `t1`、`t2`、`t3`サイクルの波形を`bit_rate`で送るタイミングを作ります。
+    ///
+    /// それぞれのサイクル数は1から16の範囲で指定してください。
+    pub const fn new(t1: u8, t2: u8, t3: u8, bit_rate: HertzU32) -> Timing {
+        assert!(
+            t1 >= 1 && t1 <= 16 && t2 >= 1 && t2 <= 16 && t3 >= 1 && t3 <= 16,
+            "T1, T2 and T3 must be within [1, 16]."
+        );
+        Timing {
+            t1,
+            t2,
+            t3,
+            bit_rate,
+        }
+    }
+
+    /// 1ビットのサイクル数を返します。
+    pub const fn cycles_per_bit(self) -> u32 {
+        (self.t1 + self.t2 + self.t3) as u32
+    }
+
+    /// 1秒あたりに送るビット数を返します。
+    pub const fn bit_rate(self) -> HertzU32 {
+        self.bit_rate
+    }
+
+    pub(crate) const fn t1(self) -> u8 {
+        self.t1
+    }
+
+    pub(crate) const fn t2(self) -> u8 {
+        self.t2
+    }
+
+    pub(crate) const fn t3(self) -> u8 {
+        self.t3
+    }
+}
@@ -0,0 +1,169 @@
+//! TM1814のドライバです。
+//!
+//! TM1814はデータ線の極性がWS2812と逆で、待機中はHighになります。
+//! ピンの出力を反転させるので、PIOのプログラムはWS2812と同じものを使います。
+//! 各フレームの先頭には、チャンネルごとの定電流の設定(C1)とそのビット反転(C2)を送ります。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::{AnyPin, OutputOverride},
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGBW, SmartLedsWrite, White};
+
+use crate::{
+    Error, PixelFormat, Stats, Strip, Timing, Ws2812Direct,
+    feed::{Encoding, Feeder, Words},
+    ops::scale_channel,
+};
+
+/// 色をTM1814のワード(`0xWWRRGGBB`)に変換します。
+#[derive(Clone, Copy)]
+struct Wrgb {
+    /// 全体の明るさ
+    fraction: u8,
+}
+
+impl Wrgb {
+    fn word(&self, color: RGBW<u8>) -> u32 {
+        let scale = |value: u8| scale_channel(value, self.fraction) as u32;
+        scale(color.a.0) << 24 | scale(color.r) << 16 | scale(color.g) << 8 | scale(color.b)
+    }
+}
+
+impl Encoding for Wrgb {
+    type Color = RGBW<u8>;
+
+    fn encode<S>(&self, _strip: &S, color: RGBW<u8>) -> Words
+    where
+        S: Strip + ?Sized,
+    {
+        self.word(color).into()
+    }
+}
+
+/// TM1814のドライバ
+///
+/// フレーム間のリセット時間(200µs以上)は呼び出し側で確保してください。
+pub struct Tm1814<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    current: RGBW<u8>,
+}
+
+impl<P, SM, I> Tm1814<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// 定電流の設定は全てのチャンネルで最大になります。
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        let mut pin = pin.into();
+        pin.set_output_override(OutputOverride::Invert);
+        let mut driver = Ws2812Direct::with_pull_threshold(
+            I::from(pin),
+            pio,
+            sm,
+            clock_freq,
+            Timing::SK6812,
+            32,
+        );
+        // 送信時間の計算のために、1ピクセル32bitの並びにしておきます。
+        driver.parts.format = PixelFormat::Grbw;
+        Self {
+            driver,
+            current: RGBW::new_alpha(63, 63, 63, White(63)),
+        }
+    }
+
+    /// チャンネルごとの定電流の設定(0から63)を変更します。
+    ///
+    /// 次に書き込むフレームから反映されます。
+    pub fn set_current(&mut self, current: RGBW<u8>) {
+        let clamp = |value: u8| value.min(63);
+        self.current = RGBW::new_alpha(
+            clamp(current.r),
+            clamp(current.g),
+            clamp(current.b),
+            White(clamp(current.a.0)),
+        );
+    }
+
+    /// チャンネルごとの定電流の設定を返します。
+    pub fn current(&self) -> RGBW<u8> {
+        self.current
+    }
+
+    /// 全体の明るさを設定します。[`Ws2812Direct::set_brightness`]を参照してください。
+    ///
+    /// 定電流の設定は変えずに、白を含む全てのチャンネルを同じ割合で暗くします。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.driver.set_brightness(brightness);
+    }
+
+    /// ストリップのLEDの数を設定します。[`Ws2812Direct::set_length`]を参照してください。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.driver.set_length(length);
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。[`Ws2812Direct::set_wait_hook`]を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.driver.set_wait_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。[`Ws2812Direct::set_wait_for_event`]を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.driver.set_wait_for_event(irq);
+    }
+
+    /// 送ったフレームなどの数を返します。[`Ws2812Direct::stats`]を参照してください。
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    /// ピンの出力の反転を戻し、ドライバを返します。
+    pub fn free(self) -> Ws2812Direct<P, SM, I> {
+        let mut driver = self.driver;
+        driver.wait_drained();
+        let mut pin = driver.parts.pin.into();
+        pin.set_output_override(OutputOverride::DontInvert);
+        driver.parts.pin = I::from(pin);
+        driver
+    }
+}
+
+impl<P, SM, I> SmartLedsWrite for Tm1814<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGBW<u8>;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let encoding = Wrgb {
+            fraction: self.driver.fraction(),
+        };
+        let frame = self
+            .driver
+            .start_frame(Feeder::with_encoding(iterator.into_iter(), encoding))?;
+        // 定電流の設定は明るさを掛けずにそのまま送ります。
+        let c1 = Wrgb { fraction: 255 }.word(self.current);
+        self.driver.write_raw(&[c1, !c1]);
+        self.driver.feed_frame(frame)
+    }
+}
@@ -0,0 +1,206 @@
+//! 外部の信号の変化に合わせてフレームを送り始めるドライバです。
+//!
+//! POV(残像)表示では、回転に合わせてホールセンサーのパルスが来た瞬間に
+//! 列のデータを送り始める必要があります。
+//! このドライバのプログラムはフレームの先頭で`wait gpio`を実行するので、
+//! CPUの割り込みの遅れに関係なく、トリガーのピンが変化してから数サイクルで送り始めます。
+//!
+//! [`Ws2812Latched`](crate::Ws2812Latched)と同じく、各ピクセルのワードの先頭に1ビットの印を付けて
+//! フレームの終わりを表すので、24ビットの[`PixelFormat`]だけに対応します。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{InstalledProgram, PIO, PIOExt, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+use crate::{Error, PixelFormat, Strip, Timing, Ws2812Direct};
+
+/// フレームを送り始めるトリガーのピンの変化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Edge {
+    /// LowからHigh
+    #[default]
+    Rising,
+    /// HighからLow
+    Falling,
+}
+
+/// トリガーを待ってからフレームを送るプログラムをインストールします。
+fn install_triggered<P: PIOExt>(
+    pio: &mut PIO<P>,
+    timing: Timing,
+    trigger: u8,
+    edge: Edge,
+) -> InstalledProgram<P> {
+    let (t1, t2, t3) = (timing.t1(), timing.t2(), timing.t3());
+    let armed = match edge {
+        Edge::Rising => 1,
+        Edge::Falling => 0,
+    };
+
+    // PIOに入れるプログラム
+    //
+    // start:
+    //     wait !ARMED gpio TRIGGER side 0
+    //     wait ARMED gpio TRIGGER  side 0
+    // next:
+    //     out x, 1                 side 0          ; 0ならフレームの終わり
+    //     jmp !x end               side 0
+    //     set y, 23                side 0
+    // bitloop:
+    //     out x, 1                 side 0 [T3 - 1]
+    //     jmp !x do_zero           side 1 [T1 - 1]
+    //     jmp y-- bitloop          side 1 [T2 - 1]
+    //     jmp next                 side 0 [T2 - 1]
+    // do_zero:
+    //     jmp y-- bitloop          side 0 [T2 - 1]
+    //     jmp next                 side 0
+    // end:
+    //     out null, 24             side 0
+    //     jmp start                side 0
+    let mut a = pio::Assembler::<32>::new_with_side_set(pio::SideSet::new(false, 1, false));
+    let mut start = a.label();
+    let mut next = a.label();
+    let mut bitloop = a.label();
+    let mut do_zero = a.label();
+    let mut end = a.label();
+    a.bind(&mut start);
+    a.wait_with_side_set(1 - armed, pio::WaitSource::GPIO, trigger, false, 0);
+    a.wait_with_side_set(armed, pio::WaitSource::GPIO, trigger, false, 0);
+    a.bind(&mut next);
+    a.out_with_side_set(pio::OutDestination::X, 1, 0);
+    a.jmp_with_side_set(pio::JmpCondition::XIsZero, &mut end, 0);
+    a.set_with_side_set(pio::SetDestination::Y, 23, 0);
+    a.bind(&mut bitloop);
+    a.out_with_delay_and_side_set(pio::OutDestination::X, 1, t3 - 1, 0);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::XIsZero, &mut do_zero, t1 - 1, 1);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::YDecNonZero, &mut bitloop, t2 - 1, 1);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::Always, &mut next, t2 - 1, 0);
+    a.bind(&mut do_zero);
+    a.jmp_with_delay_and_side_set(pio::JmpCondition::YDecNonZero, &mut bitloop, t2 - 1, 0);
+    a.jmp_with_side_set(pio::JmpCondition::Always, &mut next, 0);
+    a.bind(&mut end);
+    a.out_with_side_set(pio::OutDestination::NULL, 24, 0);
+    a.jmp_with_side_set(pio::JmpCondition::Always, &mut start, 0);
+    let program = a.assemble_program();
+
+    pio.install(&program).unwrap()
+}
+
+/// トリガーのピンの変化に合わせてフレームを送るWS2812ドライバ
+///
+/// `write`はフレームをFIFOに書き込むだけで、送信はトリガーを待ってから始まります。
+/// 次のフレームはトリガーの間隔の中でリセット時間を空けられるように書き込んでください。
+pub struct Ws2812Triggered<P, SM, I, T>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    T: AnyPin,
+{
+    driver: Ws2812Direct<P, SM, I>,
+    trigger: T,
+}
+
+impl<P, SM, I, T> Ws2812Triggered<P, SM, I, T>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    T: AnyPin,
+{
+    /// `trigger`が`edge`の向きに変化するたびに1フレームずつ送ります。
+    ///
+    /// `trigger`は入力として使える状態(プルアップなど)にしておいてください。
+    pub fn new(
+        pin: I,
+        trigger: T,
+        edge: Edge,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        Self::with_timing(pin, trigger, edge, pio, sm, clock_freq, Timing::WS2812)
+    }
+
+    /// `timing`の波形で送るように初期化します。
+    pub fn with_timing(
+        pin: I,
+        trigger: T,
+        edge: Edge,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        timing: Timing,
+    ) -> Self {
+        let trigger = trigger.into();
+        assert!(
+            trigger.id().num < 32,
+            "trigger must be one of GPIO0 to GPIO31"
+        );
+        let installed = install_triggered(pio, timing, trigger.id().num, edge);
+        let driver = Ws2812Direct::with_program(pin, installed, sm, clock_freq, timing, 25);
+        Self {
+            driver,
+            trigger: T::from(trigger),
+        }
+    }
+
+    /// `format`の並びで送るように設定します。
+    ///
+    /// [`PixelFormat::Grbw`]は使えません。
+    pub fn set_format(&mut self, format: PixelFormat) {
+        assert!(format.bits() == 24, "only 24-bit formats are supported.");
+        self.driver.parts.format = format;
+    }
+
+    /// 送っている途中やトリガーを待っているフレームを捨てます。
+    pub fn abort(&mut self) {
+        self.driver.abort();
+    }
+
+    /// ステートマシンを止め、プログラムをアンインストールして、ピンとステートマシンを返します。
+    pub fn free(self, pio: &mut PIO<P>) -> (I, T, UninitStateMachine<(P, SM)>) {
+        let (pin, sm) = self.driver.free(pio);
+        (pin, self.trigger, sm)
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。[`Ws2812Direct::set_wait_hook`]を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.driver.set_wait_hook(hook);
+    }
+
+    fn write_word(&mut self, word: u32) {
+        while !self.driver.tx.write(word) {
+            self.driver.wait();
+        }
+    }
+}
+
+impl<P, SM, I, T> SmartLedsWrite for Ws2812Triggered<P, SM, I, T>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+    T: AnyPin,
+{
+    type Color = RGB8;
+    type Error = Error;
+    /// フレームをFIFOに書き込みます。
+    ///
+    /// FIFOに入りきらない分は、トリガーが来て送り始めるまで書き込みを待ちます。
+    fn write<C, J>(&mut self, iterator: C) -> Result<(), Error>
+    where
+        C: IntoIterator<Item = J>,
+        J: Into<Self::Color>,
+    {
+        for item in iterator {
+            let word = Strip::encode(&self.driver, item.into());
+            self.write_word((1 << 31) | (word >> 1));
+        }
+        self.write_word(0);
+        Ok(())
+    }
+}
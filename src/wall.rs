@@ -0,0 +1,111 @@
+//! 1枚の大きなフレームバッファを複数の出力に分けて送るビデオウォールです。
+//!
+//! フレームバッファのどの範囲をどの出力に送るかを[`Output`]で指定すると、
+//! 出力ごとの切り出し、LEDの種類に合わせた変換、同時の送信をまとめて行います。
+//! LEDウォールやAmbilightのように、1つの画面を何本ものストリップで作る場合に使えます。
+//!
+//! ```ignore
+//! let outputs = [
+//!     Output::new(0..300),
+//!     Output::new(300..600).reversed(),
+//! ];
+//! let mut wall = VideoWall::new(StripGroup::new([a, b], cd), outputs);
+//! wall.write(&framebuffer);
+//! ```
+
+use core::{ops::Range, slice};
+
+use rp235x_hal::timer::TimerDevice;
+use smart_leds_trait::RGB8;
+
+use crate::{Strip, StripGroup};
+
+/// フレームバッファの範囲と出力の対応
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    range: Range<usize>,
+    reversed: bool,
+}
+
+impl Output {
+    /// フレームバッファの`range`を先頭から順に送ります。
+    pub fn new(range: Range<usize>) -> Self {
+        Self {
+            range,
+            reversed: false,
+        }
+    }
+
+    /// 範囲を末尾から逆順に送ります。
+    ///
+    /// ジグザグに配線したパネルで、折り返した列を送るときに使います。
+    pub fn reversed(self) -> Self {
+        Self {
+            reversed: true,
+            ..self
+        }
+    }
+}
+
+/// フレームバッファの範囲を順または逆順に返すイテレーター
+struct OutputIter<'a, C> {
+    iter: slice::Iter<'a, C>,
+    reversed: bool,
+}
+
+impl<C: Copy> Iterator for OutputIter<'_, C> {
+    type Item = C;
+
+    fn next(&mut self) -> Option<C> {
+        if self.reversed {
+            self.iter.next_back().copied()
+        } else {
+            self.iter.next().copied()
+        }
+    }
+}
+
+/// フレームバッファを複数の出力に分けて送るビデオウォール
+pub struct VideoWall<'timer, D, S, const N: usize>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    group: StripGroup<'timer, D, S, N>,
+    outputs: [Output; N],
+}
+
+impl<'timer, D, S, const N: usize> VideoWall<'timer, D, S, N>
+where
+    D: TimerDevice,
+    S: Strip,
+{
+    /// `outputs[i]`の範囲を`group`の`i`番目のストリップに送ります。
+    pub fn new(group: StripGroup<'timer, D, S, N>, outputs: [Output; N]) -> Self {
+        Self { group, outputs }
+    }
+
+    /// ストリップのまとまりと出力の対応を返します。
+    pub fn free(self) -> (StripGroup<'timer, D, S, N>, [Output; N]) {
+        (self.group, self.outputs)
+    }
+
+    /// `framebuffer`を出力ごとに切り出して、全てのストリップへ同時に送ります。
+    ///
+    /// フレームバッファに収まらない範囲は、収まる部分だけを送ります。
+    pub fn write<C>(&mut self, framebuffer: &[C])
+    where
+        C: Copy + Into<RGB8>,
+    {
+        let len = framebuffer.len();
+        let frames = self.outputs.each_ref().map(|output| {
+            let start = output.range.start.min(len);
+            let end = output.range.end.clamp(start, len);
+            OutputIter {
+                iter: framebuffer[start..end].iter(),
+                reversed: output.reversed,
+            }
+        });
+        self.group.write_all(frames);
+    }
+}
@@ -0,0 +1,106 @@
+//! フレームが届かなくなったらLEDを消すウォッチドッグです。
+//!
+//! アプリケーションが止まると、最後のフレームが点灯したままになります。
+//! 数百個のLEDが最大の明るさの白で点き続けると、発熱や電源の過負荷で危険なので、
+//! [`Watchdog`]は決めた時間の間にフレームが書き込まれなかった場合に、
+//! タイマーのアラームの割り込みから全て消灯のフレームを送ります。
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn TIMER0_IRQ_0() {
+//!     critical_section::with(|cs| WS.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt());
+//! }
+//! ```
+
+use rp235x_hal::{fugit::MicrosDurationU32, timer::Alarm};
+use smart_leds_trait::{RGB8, SmartLedsWrite};
+
+/// 決めた時間の間にフレームが書き込まれなかった場合にLEDを消すドライバ
+pub struct Watchdog<W, A>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+    A: Alarm,
+{
+    inner: W,
+    alarm: A,
+    timeout: MicrosDurationU32,
+    /// 最後に書き込んだフレームのピクセル数
+    len: usize,
+    /// 時間切れで消灯した
+    tripped: bool,
+}
+
+impl<W, A> Watchdog<W, A>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+    A: Alarm,
+{
+    /// `timeout`の間に`write`が呼ばれなければ、`alarm`の割り込みで消灯します。
+    ///
+    /// 最初のフレームを書き込むまでは見張りません。
+    pub fn new(inner: W, alarm: A, timeout: MicrosDurationU32) -> Self {
+        Self {
+            inner,
+            alarm,
+            timeout,
+            len: 0,
+            tripped: false,
+        }
+    }
+
+    /// 見張りを止めて、ドライバとアラームを返します。
+    pub fn free(mut self) -> (W, A) {
+        self.alarm.disable_interrupt();
+        self.alarm.clear_interrupt();
+        (self.inner, self.alarm)
+    }
+
+    /// 時間切れで消灯したかを返します。次のフレームを書き込むと`false`に戻ります。
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// アラームの割り込みハンドラから呼んでください。
+    ///
+    /// 時間切れの場合は、最後のフレームと同じ数のLEDを消灯します。
+    pub fn on_interrupt(&mut self) {
+        if !self.alarm.finished() {
+            return;
+        }
+        self.alarm.clear_interrupt();
+        self.alarm.disable_interrupt();
+        self.tripped = true;
+        let _ = self
+            .inner
+            .write(core::iter::repeat_n(RGB8::default(), self.len));
+    }
+}
+
+impl<W, A> SmartLedsWrite for Watchdog<W, A>
+where
+    W: SmartLedsWrite<Color = RGB8>,
+    A: Alarm,
+{
+    type Color = RGB8;
+    type Error = W::Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), W::Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        // 送っている途中に時間切れにならないように、先に見張りを止めます。
+        self.alarm.disable_interrupt();
+        let mut len = 0;
+        let result = self.inner.write(iterator.into_iter().map(|item| {
+            len += 1;
+            item.into()
+        }));
+        self.len = len;
+        self.tripped = false;
+        self.alarm.clear_interrupt();
+        if self.alarm.schedule(self.timeout).is_ok() {
+            self.alarm.enable_interrupt();
+        }
+        result
+    }
+}
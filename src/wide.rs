@@ -0,0 +1,144 @@
+//! WS2816、UCS8903などの1チャンネル16bitのLEDのドライバです。
+//!
+//! 1ピクセルは48bitなので、1回のオートプルで16bitずつ取り出し、
+//! 1チャンネルを1ワードの上位16bitとして書き込みます。
+
+use rp235x_hal::{
+    fugit::HertzU32,
+    gpio::AnyPin,
+    pio::{PIO, PIOExt, PioIRQ, StateMachineIndex, UninitStateMachine},
+};
+use smart_leds_trait::{RGB16, SmartLedsWrite};
+
+use crate::{
+    Error, PixelFormat, Stats, Strip, Timing, Ws2812Direct,
+    feed::{Encoding, Feeder, Words},
+};
+
+/// 色を1チャンネル1ワードの3ワードに変換します。
+#[derive(Clone, Copy)]
+struct Wide {
+    /// 全体の明るさ
+    fraction: u8,
+    format: PixelFormat,
+}
+
+impl Encoding for Wide {
+    type Color = RGB16;
+
+    fn encode<S>(&self, _strip: &S, color: RGB16) -> Words
+    where
+        S: Strip + ?Sized,
+    {
+        // 8bitの`ops::scale`と同じく、(fraction + 1) / 256を掛けます。
+        let word = |value: u16| (value as u32 * (self.fraction as u32 + 1)) >> 8 << 16;
+        let channels = match self.format {
+            PixelFormat::Rgb => [color.r, color.g, color.b],
+            _ => [color.g, color.r, color.b],
+        };
+        Words::new(&channels.map(word))
+    }
+}
+
+/// 1チャンネル16bitのLEDのドライバ
+///
+/// フレーム間のリセット時間(280µs以上)は呼び出し側で確保してください。
+pub struct Ws2816<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    driver: Ws2812Direct<P, SM, I>,
+}
+
+impl<P, SM, I> Ws2816<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    /// WS2816と同じGRBの並びで初期化します。
+    pub fn new(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+    ) -> Self {
+        Self::with_format(pin, pio, sm, clock_freq, PixelFormat::Grb)
+    }
+
+    /// `format`の並びで初期化します。UCS8903は[`PixelFormat::Rgb`]です。
+    ///
+    /// [`PixelFormat::Grbw`]は使えません。
+    pub fn with_format(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        format: PixelFormat,
+    ) -> Self {
+        assert!(
+            format != PixelFormat::Grbw,
+            "16-bit drivers do not support GRBW."
+        );
+        let mut driver =
+            Ws2812Direct::with_pull_threshold(pin, pio, sm, clock_freq, Timing::WS2812, 16);
+        driver.parts.format = format;
+        Self { driver }
+    }
+
+    /// 全体の明るさを設定します。[`Ws2812Direct::set_brightness`]を参照してください。
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.driver.set_brightness(brightness);
+    }
+
+    /// ストリップのLEDの数を設定します。[`Ws2812Direct::set_length`]を参照してください。
+    pub fn set_length(&mut self, length: Option<usize>) {
+        self.driver.set_length(length);
+    }
+
+    /// FIFOが空くのを待つ間に呼ぶ関数を設定します。[`Ws2812Direct::set_wait_hook`]を参照してください。
+    pub fn set_wait_hook(&mut self, hook: Option<fn()>) {
+        self.driver.set_wait_hook(hook);
+    }
+
+    /// FIFOが空くのを`wfe`で眠って待つようにします。[`Ws2812Direct::set_wait_for_event`]を参照してください。
+    pub fn set_wait_for_event(&mut self, irq: Option<PioIRQ>) {
+        self.driver.set_wait_for_event(irq);
+    }
+
+    /// 送ったフレームなどの数を返します。[`Ws2812Direct::stats`]を参照してください。
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    /// ドライバを返します。
+    pub fn free(self) -> Ws2812Direct<P, SM, I> {
+        self.driver
+    }
+}
+
+impl<P, SM, I> SmartLedsWrite for Ws2816<P, SM, I>
+where
+    I: AnyPin<Function = P::PinFunction>,
+    P: PIOExt,
+    SM: StateMachineIndex,
+{
+    type Color = RGB16;
+    type Error = Error;
+    fn write<T, C>(&mut self, iterator: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        let encoding = Wide {
+            fraction: self.driver.fraction(),
+            format: self.driver.parts.format,
+        };
+        let frame = self
+            .driver
+            .start_frame(Feeder::with_encoding(iterator.into_iter(), encoding))?;
+        self.driver.feed_frame(frame)
+    }
+}